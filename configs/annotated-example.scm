@@ -15,7 +15,7 @@
                         ;             compared to others in a list.)
                   (orientation . Vertical) 
                   (controls (Sizer (orientation . Horizontal)
-                                    ; labels just show static text.
+                                    ; labels show text, fixed or from a source.
                                    (controls (Label (label . "label1")
                                                     (proportion)
                                                     )
@@ -23,7 +23,7 @@
                                                       (proportion)
                                                       )
                                              )
-                                   (proportion 0.10000000149011612)
+                                   (proportion 0.1)
                                    )
                              (Sizer (orientation . Horizontal)
                                     ; for MouseButton 'button' values look in buildlisp.rs
@@ -41,7 +41,7 @@
                                                            (proportion)
                                                            )
                                               )
-                                    (proportion 0.10000000149011612)
+                                    (proportion 0.1)
                                     )
                             ; MouseXy moves the mouse around like a touchpad.
                              (MouseXy (label)
@@ -56,7 +56,7 @@
                               ; keys are pressed from left to right, and released right to left.
                                             (Key (label "Enter Key")
                                                  (keys EnterKey)
-                                                 (proportion 0.10000000149011612)
+                                                 (proportion 0.1)
                                                  )
                                             )
                                     (proportion) ; empty proportion is optional
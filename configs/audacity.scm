@@ -15,39 +15,47 @@
                                               )
                                     (proportion . (0.05))
                                    )
-                             (Sizer (orientation . Horizontal)
-                                     (controls (MouseButton (label . ("L"))
-                                                             (button . LeftButton)
-                                                             (proportion)
-                                                            )
-                                                (ScrollButton (label . ("Scroll"))
-                                                               (proportion)
-                                                              )
-                                                (MouseButton (label . ("R"))
-                                                              (button . RightButton)
-                                                              (proportion)
-                                                             )
-                                               )
-                                     (proportion . (0.05))
-                                    )
+                             (Include (file . "mouse-buttons.scm"))
                              (MouseXy (label)
                                        (proportion)
                                       )
-                             (Sizer (orientation . Horizontal)
-                                     (controls (Key (label . ("play/pause"))
-                                                     (keys SpaceKey)
-                                                     (proportion)
-                                                    )
-                                               (Key (label . ("undo"))
-                                                     (keys LControlKey ZKey)
-                                                     (proportion)
-                                                    )
-                                               (Key (label . ("new"))
-                                                     (keys LShiftKey RKey)
-                                                     (proportion)
-                                                    ))
-                                     (proportion . (0.05))
-                                    )
+                             (Grid (rows . 2)
+                                   (columns . 6)
+                                   (row_weights)
+                                   (column_weights)
+                                   (controls ((control Key (label . ("play/pause"))
+                                                           (keys SpaceKey)
+                                                           (proportion))
+                                              (row_span)
+                                              (column_span 2))
+                                             ((control Key (label . ("undo"))
+                                                           (keys LControlKey ZKey)
+                                                           (proportion))
+                                              (row_span)
+                                              (column_span 2))
+                                             ((control Key (label . ("new"))
+                                                           (keys LShiftKey RKey)
+                                                           (proportion))
+                                              (row_span)
+                                              (column_span 2))
+                                             ((control Slider (label . ("zoom"))
+                                                              (orientation . Horizontal)
+                                                              (action KeyPair (increase LControlKey Numrow1Key)
+                                                                              (decrease LControlKey Numrow3Key)
+                                                                              (steps . 10)
+                                                                              (presses))
+                                                              (proportion))
+                                              (row_span)
+                                              (column_span 3))
+                                             ((control Slider (label . ("scrub"))
+                                                              (orientation . Horizontal)
+                                                              (action Scroll (orientation . Horizontal)
+                                                                             (ticks . 40))
+                                                              (proportion))
+                                              (row_span)
+                                              (column_span 3)))
+                                   (proportion . (0.1))
+                                  )
                             )
                   (proportion)
                  )
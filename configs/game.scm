@@ -0,0 +1,59 @@
+((prefs (xmult . 1000.0)
+         (ymult . 1000.0)
+         (max_tap_duration . 100)
+         (show_press_duration . #f)
+         (scroll_threshold . 10)
+         (html_port . 8001)
+         (websocket_port . 9001)
+         (release_timeout . (0))
+        )
+  (gui (title . "Game")
+        (control Sizer (orientation . Vertical)
+                  (controls (Sizer (orientation . Horizontal)
+                                    (controls (Label (label . "Game")
+                                                      (proportion)
+                                                     )
+                                              (ReleaseAll (label . ("release all"))
+                                                           (proportion)
+                                                          )
+                                              )
+                                    (proportion . (0.05))
+                                   )
+                             (Sizer (orientation . Horizontal)
+                                     (controls (Joystick (label . ("move"))
+                                                          (action Keys (up WKey)
+                                                                       (down SKey)
+                                                                       (left AKey)
+                                                                       (right DKey)
+                                                                  )
+                                                          (deadzone)
+                                                          (proportion)
+                                                         )
+                                               (Joystick (label . ("look"))
+                                                          (action Mouse (speed . 800.0))
+                                                          (deadzone . (0.15))
+                                                          (proportion)
+                                                         ))
+                                     (proportion)
+                                    )
+                             (Sizer (orientation . Horizontal)
+                                     (controls (Key (label . ("jump"))
+                                                     (keys SpaceKey)
+                                                     (proportion)
+                                                    )
+                                               (MouseButton (label . ("fire"))
+                                                             (button . LeftButton)
+                                                             (proportion)
+                                                            )
+                                               (MouseButton (label . ("aim"))
+                                                             (button . RightButton)
+                                                             (proportion)
+                                                            ))
+                                     (proportion . (0.2))
+                                    )
+                            )
+                  (proportion)
+                 )
+       )
+  (colors)
+ )
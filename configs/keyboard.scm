@@ -0,0 +1,30 @@
+((prefs (xmult . 1000.0)
+         (ymult . 1000.0)
+         (max_tap_duration . 100)
+         (show_press_duration . #f)
+         (scroll_threshold . 10)
+         (html_port . 8001)
+         (websocket_port . 9001)
+        )
+  (gui (title . "Keyboard")
+        (control Sizer (orientation . Vertical)
+                  (controls (MouseXy (label)
+                                      (proportion . (0.4))
+                                     )
+                             (Sizer (orientation . Horizontal)
+                                     (controls (Keyboard (layout . Qwerty)
+                                                          (rows (Function Number Top Home Bottom Space))
+                                                          (proportion . (0.8))
+                                                         )
+                                               (Keyboard (layout . Numpad)
+                                                          (rows)
+                                                          (proportion . (0.2))
+                                                         ))
+                                     (proportion . (0.6))
+                                    )
+                            )
+                  (proportion)
+                 )
+       )
+  (colors)
+ )
@@ -0,0 +1,42 @@
+((prefs (html_port . 8001)
+        (websocket_port . 9001)
+        (mpris_label "track")  ; the Label below shows what's playing.
+        )
+ (gui (title . "Media")
+      (control Sizer (orientation . Vertical)
+                     (controls (Label (name "track")
+                                      (label . "")
+                                      (proportion 0.1))
+                               (Include (file . "mouse-buttons.scm"))
+                               (MouseXy (label)
+                                        (proportion 0.45))
+                               (Sizer (orientation . Horizontal)
+                                      (controls (Button (label "|<<")
+                                                        (actions (Mpris . Previous))
+                                                        (proportion))
+                                                (Button (label "-10s")
+                                                        (actions (Mpris Seek . -10000))
+                                                        (proportion))
+                                                (Button (label "play/pause")
+                                                        (actions (Mpris . PlayPause))
+                                                        (proportion))
+                                                (Button (label "+10s")
+                                                        (actions (Mpris Seek . 10000))
+                                                        (proportion))
+                                                (Button (label ">>|")
+                                                        (actions (Mpris . Next))
+                                                        (proportion)))
+                                      (proportion 0.2))
+                               (Sizer (orientation . Horizontal)
+                                      (controls (Button (label "vol -")
+                                                        (actions (Mpris Volume . -0.1))
+                                                        (proportion))
+                                                (Button (label "player")
+                                                        (actions (Mpris . NextPlayer))
+                                                        (proportion))
+                                                (Button (label "vol +")
+                                                        (actions (Mpris Volume . 0.1))
+                                                        (proportion)))
+                                      (proportion 0.2)))
+                     (proportion)))
+ (colors))
@@ -0,0 +1,15 @@
+(Sizer (orientation . Horizontal)
+        (controls (MouseButton (label . ("L"))
+                                (button . LeftButton)
+                                (proportion)
+                               )
+                   (ScrollButton (label . ("Scroll"))
+                                  (proportion)
+                                 )
+                   (MouseButton (label . ("R"))
+                                 (button . RightButton)
+                                 (proportion)
+                                )
+                  )
+        (proportion . (0.05))
+       )
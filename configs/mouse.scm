@@ -15,21 +15,7 @@
                                               )
                                     (proportion . (0.05))
                                    )
-                             (Sizer (orientation . Horizontal)
-                                     (controls (MouseButton (label . ("L"))
-                                                             (button . LeftButton)
-                                                             (proportion)
-                                                            )
-                                                (ScrollButton (label . ("Scroll"))
-                                                               (proportion)
-                                                              )
-                                                (MouseButton (label . ("R"))
-                                                              (button . RightButton)
-                                                              (proportion)
-                                                             )
-                                               )
-                                     (proportion . (0.05))
-                                    )
+                             (Include (file . "mouse-buttons.scm"))
                              (MouseXy (label)
                                        (proportion)
                                       )
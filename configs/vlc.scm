@@ -6,6 +6,11 @@
          (html_port . 8001)
          (websocket_port . 9001)
         )
+  (templates ((name . "key")
+               (params label keys)
+               (control Key (label $label)
+                             (keys . $keys)
+                             (proportion))))
   (gui (title . "VLC")
         (control Sizer (orientation . Vertical)
                   (controls (Sizer (orientation . Horizontal)
@@ -15,33 +20,36 @@
                                               )
                                     (proportion . (0.05))
                                    )
+                             (Include (file . "mouse-buttons.scm"))
                              (Sizer (orientation . Horizontal)
-                                     (controls (MouseButton (label . ("L"))
-                                                             (button . LeftButton)
-                                                             (proportion)
-                                                            )
-                                                (ScrollButton (label . ("Scroll"))
-                                                               (proportion)
-                                                              )
-                                                (MouseButton (label . ("R"))
-                                                              (button . RightButton)
-                                                              (proportion)
-                                                             )
-                                               )
-                                     (proportion . (0.05))
+                                     (controls (MouseXy (label)
+                                                         (proportion . (0.7))
+                                                        )
+                                               (Jog (label . ("jog"))
+                                                     (clockwise LShiftKey RightKey)
+                                                     (counter_clockwise LShiftKey LeftKey)
+                                                     (steps_per_revolution . 12)
+                                                     (proportion . (0.3))
+                                                    ))
+                                     (proportion)
                                     )
-                             (MouseXy (label)
-                                       (proportion)
-                                      )
                              (Sizer (orientation . Horizontal)
                                      (controls (Key (label . ("|<<"))
                                                      (keys NKey)
                                                      (proportion)
                                                     )
-                                               (Key (label . ("play/pause"))
-                                                     (keys SpaceKey)
-                                                     (proportion)
-                                                    )
+                                               (Toggle (name . "play")
+                                                        (on_label . ("pause"))
+                                                        (off_label . ("play"))
+                                                        (on (Keys SpaceKey))
+                                                        (off (Keys SpaceKey))
+                                                        (proportion)
+                                                       )
+                                               (Button (label . ("stop"))
+                                                        (actions (Keys SKey)
+                                                                 (ResetToggle . "play"))
+                                                        (proportion)
+                                                       )
                                                (Key (label . ("fullscreen"))
                                                      (keys FKey)
                                                      (proportion)
@@ -53,22 +61,14 @@
                                      (proportion . (0.05))
                                     )
                              (Sizer (orientation . Horizontal)
-                                     (controls (Key (label . ("<<"))
-                                                     (keys LControlKey LeftKey)
-                                                     (proportion)
-                                                    )
-                                               (Key (label . ("<"))
-                                                     (keys LShiftKey LeftKey)
-                                                     (proportion)
-                                                    )
-                                               (Key (label . (">"))
-                                                     (keys LShiftKey RightKey)
-                                                     (proportion)
-                                                    )
-                                               (Key (label . (">>"))
-                                                     (keys LControlKey RightKey)
-                                                     (proportion)
-                                                    ))
+                                     (controls (Use (template . "key")
+                                                     (args (label . "<<") (keys LControlKey LeftKey)))
+                                               (Use (template . "key")
+                                                     (args (label . "<") (keys LShiftKey LeftKey)))
+                                               (Use (template . "key")
+                                                     (args (label . ">") (keys LShiftKey RightKey)))
+                                               (Use (template . "key")
+                                                     (args (label . ">>") (keys LControlKey RightKey))))
                                      (proportion . (0.05))
                                     )
                             )
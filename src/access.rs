@@ -0,0 +1,115 @@
+// ---------------------------------------------------------------
+// who's connecting: client addresses from X-Forwarded-For when the
+// request comes through a trusted proxy, and the client allowlist.
+// ---------------------------------------------------------------
+use failure::err_msg;
+use failure::Error as FError;
+use std::net::IpAddr;
+
+// an address or a network like "192.168.1.0/24".
+#[derive(Debug, Clone)]
+pub struct AddrMatch {
+  addr: IpAddr,
+  prefix: u8,
+}
+
+impl AddrMatch {
+  pub fn parse(s: &str) -> Result<AddrMatch, FError> {
+    let mut parts = s.trim().splitn(2, '/');
+    let addr: IpAddr = parts
+      .next()
+      .unwrap_or("")
+      .parse()
+      .map_err(|_| err_msg(format!("invalid address: {}", s)))?;
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match parts.next() {
+      Some(p) => p
+        .parse::<u8>()
+        .ok()
+        .filter(|p| *p <= max)
+        .ok_or_else(|| err_msg(format!("invalid network prefix: {}", s)))?,
+      None => max,
+    };
+    Ok(AddrMatch { addr, prefix })
+  }
+
+  pub fn matches(&self, ip: &IpAddr) -> bool {
+    match (self.addr, to_canonical(ip)) {
+      (IpAddr::V4(a), IpAddr::V4(b)) => prefix_eq(&a.octets(), &b.octets(), self.prefix),
+      (IpAddr::V6(a), IpAddr::V6(b)) => prefix_eq(&a.octets(), &b.octets(), self.prefix),
+      _ => false,
+    }
+  }
+}
+
+// ipv4 clients on a dual stack socket show up as ::ffff:a.b.c.d.
+fn to_canonical(ip: &IpAddr) -> IpAddr {
+  match ip {
+    IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+      Some(v4) => IpAddr::V4(v4),
+      None => *ip,
+    },
+    _ => *ip,
+  }
+}
+
+fn prefix_eq(a: &[u8], b: &[u8], prefix: u8) -> bool {
+  let full = (prefix / 8) as usize;
+  if a[..full] != b[..full] {
+    return false;
+  }
+  let rem = prefix % 8;
+  if rem == 0 {
+    true
+  } else {
+    let mask = 0xffu8 << (8 - rem);
+    (a[full] & mask) == (b[full] & mask)
+  }
+}
+
+pub fn parse_list(l: &Option<Vec<String>>) -> Result<Vec<AddrMatch>, FError> {
+  match l {
+    Some(v) => v.iter().map(|s| AddrMatch::parse(s.as_str())).collect(),
+    None => Ok(Vec::new()),
+  }
+}
+
+pub struct Access {
+  pub trusted_proxies: Vec<AddrMatch>,
+  // empty means everyone's allowed.
+  pub allowed_clients: Vec<AddrMatch>,
+}
+
+fn any_match(l: &[AddrMatch], ip: &IpAddr) -> bool {
+  l.iter().any(|m| m.matches(ip))
+}
+
+impl Access {
+  // the real client address.  if the peer is a trusted proxy, walk
+  // X-Forwarded-For from the right, skipping over trusted proxies; the
+  // first untrusted address is the client.
+  pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> IpAddr {
+    if !any_match(&self.trusted_proxies, &peer) {
+      return peer;
+    }
+    let mut client = peer;
+    if let Some(ff) = forwarded_for {
+      for a in ff.rsplit(',') {
+        match a.trim().parse::<IpAddr>() {
+          Ok(ip) => {
+            client = ip;
+            if !any_match(&self.trusted_proxies, &ip) {
+              break;
+            }
+          }
+          Err(_) => break,
+        }
+      }
+    }
+    client
+  }
+
+  pub fn is_allowed(&self, client: &IpAddr) -> bool {
+    self.allowed_clients.is_empty() || any_match(&self.allowed_clients, client)
+  }
+}
@@ -0,0 +1,257 @@
+// ---------------------------------------------------------------
+// running the Actions of Button and Toggle controls, and keeping
+// track of toggle state.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::commands;
+use crate::held;
+use crate::http;
+use crate::input;
+use crate::mpris;
+use crate::window;
+use regex::Regex;
+use std::collections::HashMap;
+use std::time::Duration;
+use touchpage::control_nexus::ControlNexus;
+use touchpage::control_updates as cu;
+
+pub struct Actions {
+  // on/off state of Toggle controls, by toggle name.  off if missing.
+  toggles: HashMap<String, bool>,
+  commands: commands::Commands,
+  http: http::Http,
+  mpris: mpris::Mpris,
+  // Midi actions go through here, so notes are let go with the keys.
+  held: held::SharedHeld,
+  // for AppKeys.
+  provider: window::SharedProvider,
+  regexes: HashMap<String, Regex>,
+}
+
+impl Actions {
+  pub fn new(
+    mpris: mpris::Mpris,
+    held: held::SharedHeld,
+    provider: window::SharedProvider,
+  ) -> Actions {
+    Actions {
+      toggles: HashMap::new(),
+      commands: commands::Commands::new(),
+      http: http::Http::new(),
+      mpris,
+      held,
+      provider,
+      regexes: HashMap::new(),
+    }
+  }
+
+  // everything runs on press, except Http actions can go on release too,
+  // and Midi sends note off and such on release.  for a Toggle, turning
+  // on is the press of its on actions and turning off their release.
+  pub fn run(&mut self, actions: &[BL::Action], pressed: bool, cn: &ControlNexus) {
+    for a in actions {
+      match a {
+        BL::Action::Http {
+          method,
+          url,
+          headers,
+          body,
+          when,
+          timeout,
+          label,
+          show,
+        } => {
+          let send = match when.unwrap_or(BL::When::Press) {
+            BL::When::Press => pressed,
+            BL::When::Release => !pressed,
+            BL::When::Both => true,
+          };
+          if send {
+            let req = http::Request {
+              method: method.clone(),
+              url: url.clone(),
+              headers: headers.clone().unwrap_or_default(),
+              body: body.clone(),
+              timeout: *timeout,
+              label: label.clone(),
+              show: show.clone(),
+            };
+            // the same request on two buttons goes through one worker.
+            let key = serde_lexpr::to_string(a).unwrap_or(url.clone());
+            self.http.send(key, req, pressed, cn)
+          }
+        }
+        BL::Action::Midi(m) => held::lock(&self.held).midi_button(m, pressed),
+        _ if !pressed => (),
+        BL::Action::Keys(keys) => input::tap_keys(keys, 1),
+        BL::Action::AppKeys { keys, apps } => {
+          let keys = self.app_keys(keys, apps);
+          input::tap_keys(keys, 1)
+        }
+        BL::Action::Click(b) => {
+          let ib = BL::convert_mousebutton(b);
+          ib.press();
+          ib.release();
+        }
+        BL::Action::ResetToggle(name) => self.reset_toggle(name.as_str(), cn),
+        BL::Action::Mpris(m) => self.mpris.run(m),
+        BL::Action::Command {
+          program,
+          args,
+          shell,
+          timeout,
+          env,
+          cwd,
+          policy,
+          label,
+          show,
+        } => {
+          let spec = commands::Spec {
+            program: program.clone(),
+            args: args.clone(),
+            shell: shell.unwrap_or(false),
+            timeout: timeout.map(Duration::from_millis),
+            env: env.clone().unwrap_or_default(),
+            cwd: cwd.clone(),
+            policy: policy.unwrap_or(BL::CommandPolicy::Ignore),
+            label: label.clone(),
+            show: show.clone(),
+          };
+          // the same command on two buttons counts as one.
+          let key = serde_lexpr::to_string(a).unwrap_or(program.clone());
+          self.commands.run(key, spec, cn)
+        }
+      }
+    }
+  }
+
+  // the chord for the window with the focus right now.
+  fn app_keys<'a>(
+    &mut self,
+    keys: &'a Vec<BL::KeybdKey>,
+    apps: &'a Vec<BL::AppChord>,
+  ) -> &'a Vec<BL::KeybdKey> {
+    let class = match window::lock(&self.provider).active() {
+      Ok(Some(w)) => w.class,
+      Ok(None) => String::new(),
+      Err(e) => {
+        println!("error finding the active window: {}", e);
+        return keys;
+      }
+    };
+    for a in apps {
+      if !self.regexes.contains_key(&a.class) {
+        match Regex::new(a.class.as_str()) {
+          Ok(re) => {
+            self.regexes.insert(a.class.clone(), re);
+          }
+          Err(e) => {
+            println!("bad class regex {}: {}", a.class, e);
+            continue;
+          }
+        }
+      }
+      match self.regexes.get(&a.class) {
+        Some(re) if re.is_match(class.as_str()) => return &a.keys,
+        _ => (),
+      }
+    }
+    keys
+  }
+
+  pub fn on_button(&mut self, bn: &BL::ButtonName, tpname: &str, pressed: bool, cn: &ControlNexus) {
+    match bn {
+      BL::ButtonName::Actions(actions) => self.run(actions, pressed, cn),
+      BL::ButtonName::Toggle {
+        name,
+        on_label,
+        off_label,
+        on,
+        off,
+      } => {
+        if pressed {
+          let now_on = !self.toggle_state(name);
+          self.toggles.insert(name.clone(), now_on);
+          if now_on {
+            self.run(on, true, cn);
+          } else {
+            self.run(on, false, cn);
+            self.tap(off, cn);
+          }
+        }
+        // the page shows buttons as unpressed on release, so put the
+        // toggle state back either way.
+        let now_on = self.toggle_state(name);
+        show_toggle(cn, tpname, now_on, on_label, off_label);
+      }
+      // keyboard keys are handled in keyboard.rs.
+      _ => (),
+    }
+  }
+
+  // the off actions have nothing to hold, so they're pressed and let go
+  // at once.
+  fn tap(&mut self, actions: &[BL::Action], cn: &ControlNexus) {
+    self.run(actions, true, cn);
+    self.run(actions, false, cn);
+  }
+
+  fn toggle_state(&self, name: &str) -> bool {
+    *self.toggles.get(name).unwrap_or(&false)
+  }
+
+  // turning off this way lets go of the on actions too, but doesn't run
+  // the off ones.
+  pub fn reset_toggle(&mut self, name: &str, cn: &ControlNexus) {
+    let was_on = self.toggle_state(name);
+    self.toggles.insert(name.to_string(), false);
+    match find_toggle(cn, name) {
+      Some((tpname, on_label, off_label, on)) => {
+        if was_on {
+          self.run(&on, false, cn);
+        }
+        show_toggle(cn, tpname.as_str(), false, &on_label, &off_label)
+      }
+      None => println!("ResetToggle: no toggle named {}", name),
+    }
+  }
+}
+
+fn show_toggle(
+  cn: &ControlNexus,
+  tpname: &str,
+  on: bool,
+  on_label: &Option<String>,
+  off_label: &Option<String>,
+) {
+  let (state, label) = if on {
+    (cu::PressState::Pressed, on_label)
+  } else {
+    (cu::PressState::Unpressed, off_label)
+  };
+  cn.update_button(tpname, Some(state), label.clone());
+}
+
+// a toggle's touchpage name, on and off labels, and on actions.
+type FoundToggle = (String, Option<String>, Option<String>, Vec<BL::Action>);
+
+// find a toggle by its toggle name.
+fn find_toggle(cn: &ControlNexus, name: &str) -> Option<FoundToggle> {
+  let ci = match cn.ci.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  for c in ci.cm.values() {
+    match serde_lexpr::from_str::<BL::ButtonName>(c.name()) {
+      Ok(BL::ButtonName::Toggle {
+        name: n,
+        on_label,
+        off_label,
+        on,
+        off: _,
+      }) if n == name => return Some((c.name().to_string(), on_label, off_label, on)),
+      _ => (),
+    }
+  }
+  None
+}
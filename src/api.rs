@@ -0,0 +1,364 @@
+// ---------------------------------------------------------------
+// control api, for scripts and window managers.  switch profiles, set
+// label text and colors, list the connected clients, press buttons by
+// name and reload the prefs.  over the unix socket, requests and
+// replies are JSON objects, one per line:
+//
+//   {"cmd": "label", "name": "status", "text": "recording"}
+//   {"ok": true}
+//
+// the same requests go to POST <base_path>/api/command when there's a
+// token, and there's more http api in rest() below.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::profiles;
+use crate::server;
+use failure::err_msg;
+use failure::Error as FError;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use touchpage::control_nexus::{ControlNexus, ControlUpdateProcessor};
+use touchpage::control_updates as cu;
+
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use std::thread;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Request {
+  // a profile name from the prefs, or a prefs file.
+  Profile {
+    name: String,
+  },
+  Reload,
+  // text for a Label with this name.
+  Label {
+    name: String,
+    text: String,
+  },
+  // like [{"color": "Background", "hexstring": "202020"}]
+  Colors {
+    colors: Vec<BL::SetColor>,
+  },
+  Clients,
+  // press and release a button by its label, or a toggle by its name.
+  Trigger {
+    name: String,
+    state: Option<TriggerState>,
+  },
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerState {
+  Press,
+  Release,
+  Tap,
+}
+
+// the page's update processor, shared so the api can run buttons through
+// it too.
+pub type Processor = Arc<Mutex<dyn ControlUpdateProcessor>>;
+
+#[derive(Clone)]
+pub struct Api {
+  pub cn: ControlNexus,
+  pub processor: Processor,
+  pub profiles: profiles::SharedProfiles,
+  pub clients: server::SharedClients,
+  // the startup prefs, less the token.  profile switches only change the
+  // gui and colors, so these stay the prefs in use.
+  pub prefs: Value,
+}
+
+impl Api {
+  // a reply line for a request line.
+  pub fn handle_line(&self, line: &str) -> String {
+    let reply = match serde_json::from_str::<Request>(line) {
+      Ok(req) => self.handle(req),
+      Err(e) => error_reply(format!("bad request: {}", e)),
+    };
+    reply.to_string()
+  }
+
+  // {"ok": true} and whatever the request returns, or {"ok": false,
+  // "error": "..."}.
+  pub fn handle(&self, req: Request) -> Value {
+    match self.run(req) {
+      Ok(mut fields) => {
+        fields.insert("ok".to_string(), Value::Bool(true));
+        Value::Object(fields)
+      }
+      Err(e) => error_reply(format!("{}", e)),
+    }
+  }
+
+  fn run(&self, req: Request) -> Result<Map<String, Value>, FError> {
+    let mut fields = Map::new();
+    match req {
+      Request::Profile { name } => {
+        profiles::lock(&self.profiles).switch(name.as_str())?;
+        fields.insert("profile".to_string(), Value::String(name));
+      }
+      Request::Reload => profiles::lock(&self.profiles).reload()?,
+      Request::Label { name, text } => {
+        if !self.has_name(name.as_str()) {
+          return Err(err_msg(format!("no label named {}", name)));
+        }
+        self.cn.update_label(name.as_str(), text.as_str());
+      }
+      Request::Colors { colors } => profiles::lock(&self.profiles).set_colors(colors)?,
+      Request::Clients => {
+        let clients = server::lock_clients(&self.clients)
+          .iter()
+          .map(|c| {
+            let mut m = Map::new();
+            m.insert("from".to_string(), Value::String(c.from.clone()));
+            m.insert(
+              "seconds".to_string(),
+              Value::from(c.since.elapsed().as_secs()),
+            );
+            Value::Object(m)
+          })
+          .collect();
+        fields.insert("clients".to_string(), Value::Array(clients));
+      }
+      Request::Trigger { name, state } => {
+        self.trigger(name.as_str(), state.unwrap_or(TriggerState::Tap))?
+      }
+    }
+    Ok(fields)
+  }
+
+  fn has_name(&self, name: &str) -> bool {
+    let ci = match self.cn.ci.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    ci.cnm.contains_key(name)
+  }
+
+  // goes to the page as well, so the button shows pressed.
+  pub fn trigger(&self, name: &str, state: TriggerState) -> Result<(), FError> {
+    let id = match find_button(&self.cn, name) {
+      Some(id) => id,
+      None => return Err(err_msg(format!("no button named {}", name))),
+    };
+    let states = match state {
+      TriggerState::Press => vec![cu::PressState::Pressed],
+      TriggerState::Release => vec![cu::PressState::Unpressed],
+      TriggerState::Tap => vec![cu::PressState::Pressed, cu::PressState::Unpressed],
+    };
+    for s in states {
+      let msg = cu::UpdateMsg::Button {
+        control_id: id.clone(),
+        state: Some(s),
+        label: None,
+      };
+      self.cn.update(&msg);
+      let mut cn = self.cn.clone();
+      let mut p = match self.processor.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+      };
+      p.on_update_received(&msg, &mut cn);
+    }
+    Ok(())
+  }
+
+  // the http api.  returns the status and the reply.
+  //
+  //   GET  api/controls        the controls on the page
+  //   GET  api/prefs           the prefs in use, from startup
+  //   GET  api/state           connected clients and the current profile
+  //   POST api/trigger/<name>  press and release a button, or
+  //                            ?state=press or ?state=release
+  //   POST api/command         a Request as JSON; profiles by name only,
+  //                            no prefs file paths from the network
+  pub fn rest(&self, method: &str, route: &str, path: &str, body: &[u8]) -> (&'static str, Value) {
+    let reply = match (method, route) {
+      ("GET", "controls") => return ("200 OK", self.controls()),
+      ("GET", "prefs") => return ("200 OK", self.prefs.clone()),
+      ("GET", "state") => return ("200 OK", self.state()),
+      ("POST", "command") => match serde_json::from_slice::<Request>(body) {
+        Ok(Request::Profile { name })
+          if !profiles::lock(&self.profiles).is_named(name.as_str()) =>
+        {
+          error_reply(format!("no profile named {}", name))
+        }
+        Ok(req) => self.handle(req),
+        Err(e) => error_reply(format!("bad request: {}", e)),
+      },
+      ("POST", r) if r.starts_with("trigger/") => {
+        let state = match server::query_param(path, "state") {
+          Some(s) => match serde_json::from_value(Value::String(s.clone())) {
+            Ok(st) => Some(st),
+            Err(_) => return ("400 Bad Request", error_reply(format!("bad state: {}", s))),
+          },
+          None => None,
+        };
+        self.handle(Request::Trigger {
+          name: server::percent_decode(&r["trigger/".len()..]),
+          state,
+        })
+      }
+      (_, "controls") | (_, "prefs") | (_, "state") | (_, "command") => {
+        return (
+          "405 Method Not Allowed",
+          error_reply("method not allowed".to_string()),
+        )
+      }
+      _ => return ("404 Not Found", error_reply("not found".to_string())),
+    };
+    if reply.get("ok") == Some(&Value::Bool(true)) {
+      ("200 OK", reply)
+    } else {
+      ("400 Bad Request", reply)
+    }
+  }
+
+  // id, type and label of each control, in page order.  'name' is what
+  // trigger and label requests take.
+  fn controls(&self) -> Value {
+    let ci = match self.cn.ci.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut ids: Vec<&Vec<i32>> = ci.cm.keys().collect();
+    ids.sort();
+    let controls = ids
+      .into_iter()
+      .filter_map(|id| ci.cm.get(id))
+      .filter(|c| c.control_type() != "sizer")
+      .map(|c| {
+        let json = c.as_json();
+        let label = json.get("label").and_then(|l| l.as_str());
+        let name = match c.control_type() {
+          "button" => match serde_lexpr::from_str::<BL::ButtonName>(c.name()) {
+            Ok(BL::ButtonName::Toggle { name, .. }) => Some(name),
+            _ => label.map(|l| l.to_string()),
+          },
+          "label" if !c.name().is_empty() => Some(c.name().to_string()),
+          _ => None,
+        };
+        let mut m = Map::new();
+        m.insert("id".to_string(), Value::from(c.control_id().clone()));
+        m.insert("type".to_string(), Value::from(c.control_type()));
+        m.insert("label".to_string(), Value::from(label));
+        m.insert("name".to_string(), Value::from(name));
+        Value::Object(m)
+      })
+      .collect();
+    Value::Array(controls)
+  }
+
+  fn state(&self) -> Value {
+    let mut m = match self.run(Request::Clients) {
+      Ok(m) => m,
+      Err(_) => Map::new(),
+    };
+    let profiles = profiles::lock(&self.profiles);
+    m.insert("profile".to_string(), Value::from(profiles.name()));
+    m.insert("profiles".to_string(), Value::from(profiles.names()));
+    Value::Object(m)
+  }
+}
+
+fn error_reply(e: String) -> Value {
+  let mut m = Map::new();
+  m.insert("ok".to_string(), Value::Bool(false));
+  m.insert("error".to_string(), Value::String(e));
+  Value::Object(m)
+}
+
+// the id of the button with this label, or the toggle with this name.
+// the first one on the page if there's more than one.
+pub fn find_button(cn: &ControlNexus, name: &str) -> Option<Vec<i32>> {
+  let ci = match cn.ci.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  ci.cm
+    .iter()
+    .filter(|(_, c)| c.control_type() == "button")
+    .filter(|(_, c)| {
+      let label = c.as_json().get("label").and_then(|l| l.as_str()) == Some(name);
+      label
+        || match serde_lexpr::from_str::<BL::ButtonName>(c.name()) {
+          Ok(BL::ButtonName::Toggle { name: n, .. }) => n == name,
+          _ => false,
+        }
+    })
+    .map(|(id, _)| id.clone())
+    .min()
+}
+
+// listen on the unix socket, a thread per connection.
+#[cfg(unix)]
+pub fn start_socket(path: &str, api: Api) -> Result<(), FError> {
+  // a socket left over from last time is in the way.
+  match fs::symlink_metadata(path) {
+    Ok(m) if m.file_type().is_socket() => fs::remove_file(path)?,
+    _ => (),
+  }
+  // anyone who can use the socket can press keys.  it's made in a
+  // directory only we can get into, and only moved to 'path' once it's
+  // 0600, so nobody else can ever connect.
+  let dir = format!("{}.{}.tmp", path, std::process::id());
+  fs::DirBuilder::new().mode(0o700).create(dir.as_str())?;
+  let tmp = Path::new(dir.as_str()).join("socket");
+  let bound = UnixListener::bind(&tmp).and_then(|l| {
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+    fs::rename(&tmp, path)?;
+    Ok(l)
+  });
+  let _ = fs::remove_file(&tmp);
+  let _ = fs::remove_dir(dir.as_str());
+  let listener = bound?;
+  println!("control socket at {}", path);
+  thread::spawn(move || {
+    for stream in listener.incoming() {
+      match stream {
+        Ok(s) => {
+          let api = api.clone();
+          thread::spawn(move || match serve(s, &api) {
+            Ok(_) => (),
+            Err(e) => println!("control socket error: {}", e),
+          });
+        }
+        Err(e) => println!("error accepting control socket connection: {}", e),
+      }
+    }
+  });
+  Ok(())
+}
+
+#[cfg(unix)]
+fn serve(stream: UnixStream, api: &Api) -> Result<(), FError> {
+  let reader = BufReader::new(stream.try_clone()?);
+  let mut writer = stream;
+  for line in reader.lines() {
+    let line = line?;
+    if line.trim().is_empty() {
+      continue;
+    }
+    writeln!(writer, "{}", api.handle_line(line.as_str()))?;
+  }
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn start_socket(_path: &str, _api: Api) -> Result<(), FError> {
+  Err(err_msg("the control socket is only on unix"))
+}
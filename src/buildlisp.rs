@@ -1,19 +1,26 @@
 // use touchpage::controls::Orientation::{Horizontal, Vertical};
 // use touchpage::controls::Orientation;
+use crate::grid;
+use crate::keyboard;
 use failure::Error as FError;
 use inputbot as I;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use touchpage::controls as C;
 use touchpage::guibuilder as G;
 
+// everything but the gui can be left out of the prefs file.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Settings {
+  #[serde(default)]
   pub prefs: Prefs,
   pub gui: Gui,
   pub colors: Option<Vec<SetColor>>,
 }
 
+// missing prefs get the values from Prefs::default().
 #[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
 pub struct Prefs {
   pub xmult: f32,
   pub ymult: f32,
@@ -22,6 +29,134 @@ pub struct Prefs {
   pub scroll_threshold: i32,
   pub html_port: i32,
   pub websocket_port: i32,
+  // advertise the page over mDNS.  on unless turned off with (mdns #f).
+  pub mdns: Option<bool>,
+  // interface names to advertise on, like (mdns_interfaces ("eth0" "lo")).
+  // all interfaces except loopback if empty.
+  pub mdns_interfaces: Option<Vec<String>>,
+  // serve the page and the websocket both on html_port.  off by default,
+  // which uses websocket_port for the websocket.
+  pub single_port: Option<bool>,
+  // path the page is served under, for reverse proxies.  like "/mousepage/".
+  pub base_path: Option<String>,
+  // url the page should open its websocket on, if it's not the page's host
+  // and websocket_port (or the page url itself in single port mode).
+  // can be relative to the page, like "/mousepage/ws".
+  pub public_websocket_url: Option<String>,
+  // proxies whose X-Forwarded-For header is believed.  addresses or
+  // networks, like ("127.0.0.1" "10.0.0.0/8").
+  pub trusted_proxies: Option<Vec<String>>,
+  // only these clients may connect, if given.  addresses or networks.
+  pub allowed_clients: Option<Vec<String>>,
+  // release held keys and buttons after this many ms without any messages
+  // from the page.  off if empty or 0.
+  pub release_timeout: Option<u32>,
+  // MPRIS player for Mpris actions, like "vlc" or "spotify".  if empty, or
+  // that player isn't running, whichever player is playing.
+  pub mpris_player: Option<String>,
+  // name of a Label to show the current track in.
+  pub mpris_label: Option<String>,
+  // send OSC messages for buttons, sliders and xy pads.
+  pub osc: Option<OscPrefs>,
+  // name of the virtual MIDI port, if the gui has Midi controls.
+  // "mousepage" if empty.
+  pub midi_port: Option<String>,
+  // path of a unix socket for scripts to control mousepage through.  no
+  // socket if empty.
+  pub control_socket: Option<String>,
+  // other prefs files to switch the gui to, by name, like
+  // (profiles (("vlc" . "vlc.scm"))).  relative to this prefs file.
+  pub profiles: Option<BTreeMap<String, String>>,
+  // clients must give this token.  it goes in the page url as
+  // ?token=..., and the api also takes it as "Authorization: Bearer ...".
+  // no token needed if empty.
+  pub token: Option<String>,
+  // where to find the window with the focus, for ActiveWindow labels and
+  // follow_window.  X11 if empty.
+  pub window_provider: Option<WindowProvider>,
+  // switch profiles to match the window with the focus.  the first rule
+  // matching the window's class is used.
+  pub follow_window: Option<Vec<WindowRule>>,
+}
+
+impl Default for Prefs {
+  fn default() -> Prefs {
+    Prefs {
+      xmult: 1000.0,
+      ymult: 1000.0,
+      max_tap_duration: 100,
+      show_press_duration: false,
+      scroll_threshold: 10,
+      html_port: 8000,
+      websocket_port: 9000,
+      mdns: None,
+      mdns_interfaces: None,
+      single_port: None,
+      base_path: None,
+      public_websocket_url: None,
+      trusted_proxies: None,
+      allowed_clients: None,
+      release_timeout: None,
+      mpris_player: None,
+      mpris_label: None,
+      osc: None,
+      midi_port: None,
+      control_socket: None,
+      profiles: None,
+      token: None,
+      window_provider: None,
+      follow_window: None,
+    }
+  }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum WindowProvider {
+  // _NET_ACTIVE_WINDOW from the X server in $DISPLAY.
+  X11,
+  // a file with the window's class on the first line and its title on the
+  // second.
+  File(String),
+}
+
+// 'class' is a regex for the window's WM_CLASS class, like "^vlc$", and
+// 'profile' a name from 'profiles' or a prefs file.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WindowRule {
+  pub class: String,
+  pub profile: String,
+}
+
+// where OSC messages go, and what they look like.  the address is a
+// pattern with {kind} (button, slider or xy), {label} (the control's
+// label, or its id if it has none) and {id} (like 2/0) filled in.
+// "/mousepage/{kind}/{label}" if empty.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OscPrefs {
+  pub host: String,
+  pub port: u16,
+  pub mode: Option<OscMode>,
+  pub address: Option<String>,
+  pub scale: Option<Vec<OscScale>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum OscMode {
+  // OSC as well as the usual keys and mouse; the default.
+  Also,
+  // just OSC.
+  Only,
+}
+
+// argument range for addresses matching 'address', which can end in *.
+// values go from 0.0 to 1.0 otherwise; buttons are 1 pressed, 0 not.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OscScale {
+  pub address: String,
+  pub min: f32,
+  pub max: f32,
+  // send ints instead of floats.
+  pub integer: Option<bool>,
 }
 
 // ---------------------------------------------------------------
@@ -34,7 +169,7 @@ pub enum Orientation {
   Vertical,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum Color {
   Controls,
   Labels,
@@ -258,12 +393,294 @@ pub struct Gui {
   pub control: Control,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct SetColor {
   pub color: Color,
   pub hexstring: String,
 }
 
+// what moving a Slider does.  the relative ones count steps from
+// where the slider was pressed; negative counts reverse the direction.
+#[derive(Deserialize, Serialize, Debug)]
+pub enum SliderAction {
+  // scroll 'ticks' clicks over the length of the slider.
+  Scroll {
+    orientation: Orientation,
+    ticks: i32,
+  },
+  // tap the increase or decrease keys, 'steps' times over the length of
+  // the slider, and 'presses' times per step (default 1).
+  KeyPair {
+    increase: Vec<KeybdKey>,
+    decrease: Vec<KeybdKey>,
+    steps: i32,
+    presses: Option<u32>,
+  },
+  // move the mouse 'pixels' over the length of the slider.
+  Mouse {
+    orientation: Orientation,
+    pixels: i32,
+  },
+  // run a program when the slider value changes, with "{value}" in the
+  // args replaced by the slider position, 0 to 100.  for setting the volume:
+  // (Command (program . "amixer") (args "set" "Master" "{value}%"))
+  Command {
+    program: String,
+    args: Vec<String>,
+  },
+  // send a MIDI control change, min to max (0 to 127 if empty) over the
+  // length of the slider.  channels are 1 to 16.
+  Midi {
+    channel: u8,
+    cc: u8,
+    min: Option<u8>,
+    max: Option<u8>,
+  },
+}
+
+// things a Button or Toggle can do.  in the prefs file, like
+// (on (Keys LControlKey ZKey) (Click . LeftButton) (ResetToggle . "play"))
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum Action {
+  // tap a key chord.
+  Keys(Vec<KeybdKey>),
+  // tap a key chord picked by the program with the focus: the keys of
+  // the first of 'apps' whose regex matches the window's class, or
+  // 'keys' if none do.
+  // (AppKeys (keys SpaceKey) (apps ((class . "(?i)audacity") (keys LShiftKey SpaceKey))))
+  AppKeys {
+    keys: Vec<KeybdKey>,
+    apps: Vec<AppChord>,
+  },
+  // click a mouse button.
+  Click(MouseButton),
+  // switch the named Toggle off, without running its 'off' actions.
+  ResetToggle(String),
+  // control a media player.
+  Mpris(MprisAction),
+  // send MIDI through the virtual port.
+  Midi(MidiMessage),
+  // send an http request, on press unless 'when' says otherwise.  "{event}"
+  // in the url, headers and body becomes "press" or "release", and
+  // "{env:NAME}" the environment variable NAME, for keeping tokens out of
+  // the prefs.  'timeout' is in ms, 5000 if empty.  the response status
+  // goes in the Label named by 'label', formatted by 'show' with "{status}"
+  // and "{body}" replaced.
+  // (Http (url . "http://localhost:8123/api/services/light/toggle")
+  //       (headers (("Authorization" . "Bearer {env:HA_TOKEN}"))))
+  Http {
+    method: Option<String>,
+    url: String,
+    headers: Option<BTreeMap<String, String>>,
+    body: Option<String>,
+    when: Option<When>,
+    timeout: Option<u64>,
+    label: Option<String>,
+    show: Option<String>,
+  },
+  // run a program, without a shell unless 'shell' is #t, in which case
+  // 'program' is a shell command line and 'args' are its $1, $2 ...
+  // 'timeout' is in ms.  'label' is the name of a Label to show the exit
+  // status and output in, formatted by 'show' with "{status}" and
+  // "{stdout}" replaced.
+  // (Command (program . "notify-send") (args "hello") (label . "status"))
+  Command {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    shell: Option<bool>,
+    timeout: Option<u64>,
+    env: Option<BTreeMap<String, String>>,
+    cwd: Option<String>,
+    policy: Option<CommandPolicy>,
+    label: Option<String>,
+    show: Option<String>,
+  },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AppChord {
+  pub class: String,
+  pub keys: Vec<KeybdKey>,
+}
+
+// MIDI for Button actions, sent on press and on release.  channels are
+// 1 to 16.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum MidiMessage {
+  // note on when pressed, note off when released.  velocity 127 if empty.
+  Note {
+    channel: u8,
+    note: u8,
+    velocity: Option<u8>,
+  },
+  // control change to 'on' (127 if empty) when pressed and 'off' (0 if
+  // empty) when released.
+  Cc {
+    channel: u8,
+    cc: u8,
+    on: Option<u8>,
+    off: Option<u8>,
+  },
+}
+
+// when a Button's Http action is sent.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum When {
+  Press,
+  Release,
+  Both,
+}
+
+// media player control over D-Bus, like (Mpris . PlayPause) or
+// (Mpris Seek . 10000).
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum MprisAction {
+  PlayPause,
+  Play,
+  Pause,
+  Stop,
+  Next,
+  Previous,
+  // ms forward, or back if negative.
+  Seek(i64),
+  // change the volume by this much; full volume is 1.0.
+  Volume(f64),
+  // control this player from now on, like "vlc".
+  SelectPlayer(String),
+  // switch to the next running player.
+  NextPlayer,
+}
+
+// what pressing a Command button does while the command is still running.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum CommandPolicy {
+  // do nothing; the default.
+  Ignore,
+  // kill it and start again.
+  Kill,
+  // run it again when it's done, once per press.
+  Queue,
+}
+
+// touchpage names for Button and Toggle controls.  tagged so they can't
+// be mistaken for the key lists and mouse buttons of the other buttons.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum ButtonName {
+  Actions(Vec<Action>),
+  Toggle {
+    name: String,
+    on_label: Option<String>,
+    off_label: Option<String>,
+    on: Vec<Action>,
+    off: Vec<Action>,
+  },
+  // a key of a Keyboard control.
+  KeyboardKey {
+    keys: Vec<KeybdKey>,
+    label: String,
+    shift_label: String,
+  },
+  KeyboardShift,
+}
+
+// layouts for the Keyboard control.  labels are for a host set to the
+// same layout.
+#[derive(Deserialize, Serialize, Debug)]
+pub enum KeyboardLayout {
+  Qwerty,
+  Azerty,
+  Dvorak,
+  // numeric keypad.
+  Numpad,
+  // escape and F1 to F12.
+  FunctionRow,
+}
+
+// rows of the Qwerty, Azerty and Dvorak layouts, for picking which ones
+// a Keyboard control shows, and in what order.
+#[derive(Deserialize, Serialize, Debug)]
+pub enum KeyboardRow {
+  // escape and F1 to F12.
+  Function,
+  Number,
+  Top,
+  Home,
+  Bottom,
+  // ctrl, alt, space and the arrow keys.
+  Space,
+}
+
+// what pushing a Joystick does.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum JoystickAction {
+  // move the cursor, at up to 'speed' pixels per second at full tilt.
+  Mouse {
+    speed: f32,
+  },
+  // hold direction keys, two at once on the diagonals.  for arrows,
+  // (Keys (up UpKey) (down DownKey) (left LeftKey) (right RightKey))
+  Keys {
+    up: Vec<KeybdKey>,
+    down: Vec<KeybdKey>,
+    left: Vec<KeybdKey>,
+    right: Vec<KeybdKey>,
+  },
+}
+
+// what a Label can show, kept up to date.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum LabelSource {
+  // output of a command, like (Command (program . "date") (args "+%a")).
+  // killed after timeout ms, 10 seconds if left out.
+  Command {
+    program: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    timeout: Option<u64>,
+  },
+  // contents of a file, like (File . "/sys/class/thermal/thermal_zone0/temp").
+  File(String),
+  // the time, in strftime format, like (Clock . "%H:%M").
+  Clock(String),
+  // title of the window with the focus.
+  ActiveWindow,
+  // charge and status of the battery, like "87% Charging".
+  Battery,
+}
+
+// a Label with a source, by its touchpage name.
+pub struct SourcedLabel {
+  pub name: String,
+  pub source: LabelSource,
+  pub interval: Option<u64>,
+}
+
+// touchpage names for xy controls other than MouseXy, which is just "xy".
+#[derive(Deserialize, Serialize, Debug)]
+pub enum XyName {
+  Joystick {
+    action: JoystickAction,
+    deadzone: Option<f32>,
+  },
+  Jog {
+    clockwise: Vec<KeybdKey>,
+    counter_clockwise: Vec<KeybdKey>,
+    steps_per_revolution: u32,
+  },
+  Midi {
+    channel: u8,
+    x_cc: u8,
+    y_cc: u8,
+    min: Option<u8>,
+    max: Option<u8>,
+  },
+}
+
+// touchpage control name for the release all button.
+pub const RELEASE_ALL: &str = "ReleaseAll";
+
 #[derive(Deserialize, Serialize, Debug)]
 pub enum Control {
   MouseButton {
@@ -284,8 +701,80 @@ pub enum Control {
     keys: Vec<KeybdKey>,
     proportion: Option<f32>,
   },
+  // 'name' is for commands and such to show things in the label.  with a
+  // 'source' the label shows that instead, checked every 'interval' ms,
+  // 1000 if empty.
   Label {
+    name: Option<String>,
     label: String,
+    source: Option<LabelSource>,
+    interval: Option<u64>,
+    proportion: Option<f32>,
+  },
+  Slider {
+    label: Option<String>,
+    orientation: Orientation,
+    action: SliderAction,
+    proportion: Option<f32>,
+  },
+  // runs its actions when pressed.
+  Button {
+    label: Option<String>,
+    actions: Vec<Action>,
+    proportion: Option<f32>,
+  },
+  // alternates between on and off with each press, running the 'on' or
+  // 'off' actions and showing on_label or off_label.  'name' is for
+  // ResetToggle.
+  Toggle {
+    name: String,
+    on_label: Option<String>,
+    off_label: Option<String>,
+    #[serde(default)]
+    on: Vec<Action>,
+    #[serde(default)]
+    off: Vec<Action>,
+    proportion: Option<f32>,
+  },
+  // jog wheel; circling the center taps the clockwise or
+  // counter_clockwise keys, steps_per_revolution times per turn.
+  Jog {
+    label: Option<String>,
+    clockwise: Vec<KeybdKey>,
+    counter_clockwise: Vec<KeybdKey>,
+    steps_per_revolution: u32,
+    proportion: Option<f32>,
+  },
+  // held like a game stick; see JoystickAction.  deadzone is the
+  // fraction of the radius around the center that does nothing, 0.2 if
+  // empty.
+  Joystick {
+    label: Option<String>,
+    action: JoystickAction,
+    deadzone: Option<f32>,
+    proportion: Option<f32>,
+  },
+  // an xy pad sending MIDI control changes for x and y, min to max (0
+  // to 127 if empty) across the pad.  the top is max for y.
+  MidiXy {
+    label: Option<String>,
+    channel: u8,
+    x_cc: u8,
+    y_cc: u8,
+    min: Option<u8>,
+    max: Option<u8>,
+    proportion: Option<f32>,
+  },
+  // a whole keyboard.  rows are the KeyboardRows to show, for the letter
+  // layouts; Number, Top, Home, Bottom and Space if empty.
+  Keyboard {
+    layout: KeyboardLayout,
+    rows: Option<Vec<KeyboardRow>>,
+    proportion: Option<f32>,
+  },
+  // panic button; releases all held keys and mouse buttons.
+  ReleaseAll {
+    label: Option<String>,
     proportion: Option<f32>,
   },
   Sizer {
@@ -293,6 +782,26 @@ pub enum Control {
     controls: Vec<Control>,
     proportion: Option<f32>,
   },
+  // rows x columns cells, filled left to right and top to bottom by the
+  // controls.  weights size the rows and columns relative to each other,
+  // all the same if empty.  proportions of the controls are ignored.
+  Grid {
+    rows: u32,
+    columns: u32,
+    row_weights: Option<Vec<f32>>,
+    column_weights: Option<Vec<f32>>,
+    controls: Vec<GridCell>,
+    proportion: Option<f32>,
+  },
+}
+
+// a child of a Grid, covering row_span x column_span cells, 1 if empty.
+// like ((control Key (label . ("a")) (keys AKey) (proportion)) (row_span) (column_span 2))
+#[derive(Deserialize, Serialize, Debug)]
+pub struct GridCell {
+  pub control: Control,
+  pub row_span: Option<u32>,
+  pub column_span: Option<u32>,
 }
 
 fn get_proportion(c: &Control) -> Option<f32> {
@@ -316,6 +825,59 @@ fn get_proportion(c: &Control) -> Option<f32> {
       proportion,
     } => *proportion,
     Control::Label {
+      name: _,
+      label: _,
+      source: _,
+      interval: _,
+      proportion,
+    } => *proportion,
+    Control::Slider {
+      label: _,
+      orientation: _,
+      action: _,
+      proportion,
+    } => *proportion,
+    Control::Button {
+      label: _,
+      actions: _,
+      proportion,
+    } => *proportion,
+    Control::Toggle {
+      name: _,
+      on_label: _,
+      off_label: _,
+      on: _,
+      off: _,
+      proportion,
+    } => *proportion,
+    Control::Jog {
+      label: _,
+      clockwise: _,
+      counter_clockwise: _,
+      steps_per_revolution: _,
+      proportion,
+    } => *proportion,
+    Control::Joystick {
+      label: _,
+      action: _,
+      deadzone: _,
+      proportion,
+    } => *proportion,
+    Control::MidiXy {
+      label: _,
+      channel: _,
+      x_cc: _,
+      y_cc: _,
+      min: _,
+      max: _,
+      proportion,
+    } => *proportion,
+    Control::Keyboard {
+      layout: _,
+      rows: _,
+      proportion,
+    } => *proportion,
+    Control::ReleaseAll {
       label: _,
       proportion,
     } => *proportion,
@@ -324,6 +886,14 @@ fn get_proportion(c: &Control) -> Option<f32> {
       controls: _,
       proportion,
     } => *proportion,
+    Control::Grid {
+      rows: _,
+      columns: _,
+      row_weights: _,
+      column_weights: _,
+      controls: _,
+      proportion,
+    } => *proportion,
   }
 }
 
@@ -348,9 +918,100 @@ pub fn add_control<'a>(gui: &'a mut G::Gui, control: &Control) -> Result<&'a mut
       proportion: _,
     } => gui.add_button(serde_lexpr::to_string(keys)?, label.as_ref().cloned()),
     Control::Label {
+      name,
+      label,
+      source,
+      interval: _,
+      proportion: _,
+    } => gui.add_label(label_name(name, source), label.clone()),
+    Control::Slider {
+      label,
+      orientation,
+      action,
+      proportion: _,
+    } => gui.add_slider(
+      serde_lexpr::to_string(action)?,
+      label.as_ref().cloned(),
+      convert_orientation(orientation),
+    ),
+    Control::Button {
+      label,
+      actions,
+      proportion: _,
+    } => gui.add_button(
+      serde_lexpr::to_string(&ButtonName::Actions(actions.clone()))?,
+      label.as_ref().cloned(),
+    ),
+    Control::Toggle {
+      name,
+      on_label,
+      off_label,
+      on,
+      off,
+      proportion: _,
+    } => gui.add_button(
+      serde_lexpr::to_string(&ButtonName::Toggle {
+        name: name.clone(),
+        on_label: on_label.clone(),
+        off_label: off_label.clone(),
+        on: on.clone(),
+        off: off.clone(),
+      })?,
+      off_label.as_ref().cloned(),
+    ),
+    Control::Jog {
+      label,
+      clockwise,
+      counter_clockwise,
+      steps_per_revolution,
+      proportion: _,
+    } => gui.add_xy(
+      serde_lexpr::to_string(&XyName::Jog {
+        clockwise: clockwise.clone(),
+        counter_clockwise: counter_clockwise.clone(),
+        steps_per_revolution: *steps_per_revolution,
+      })?,
+      label.as_ref().cloned(),
+    ),
+    Control::Joystick {
       label,
+      action,
+      deadzone,
       proportion: _,
-    } => gui.add_label("".to_string(), label.clone()),
+    } => gui.add_xy(
+      serde_lexpr::to_string(&XyName::Joystick {
+        action: action.clone(),
+        deadzone: *deadzone,
+      })?,
+      label.as_ref().cloned(),
+    ),
+    Control::MidiXy {
+      label,
+      channel,
+      x_cc,
+      y_cc,
+      min,
+      max,
+      proportion: _,
+    } => gui.add_xy(
+      serde_lexpr::to_string(&XyName::Midi {
+        channel: *channel,
+        x_cc: *x_cc,
+        y_cc: *y_cc,
+        min: *min,
+        max: *max,
+      })?,
+      label.as_ref().cloned(),
+    ),
+    Control::Keyboard {
+      layout,
+      rows,
+      proportion: _,
+    } => keyboard::add_keyboard(gui, layout, rows),
+    Control::ReleaseAll {
+      label,
+      proportion: _,
+    } => gui.add_button(RELEASE_ALL.to_string(), label.as_ref().cloned()),
     Control::Sizer {
       orientation,
       controls,
@@ -372,6 +1033,14 @@ pub fn add_control<'a>(gui: &'a mut G::Gui, control: &Control) -> Result<&'a mut
       }
       g.end_sizer()
     }
+    Control::Grid {
+      rows,
+      columns,
+      row_weights,
+      column_weights,
+      controls,
+      proportion: _,
+    } => grid::add_grid(gui, *rows, *columns, row_weights, column_weights, controls),
   }
 }
 
@@ -387,3 +1056,82 @@ pub fn build_gui(gui: Gui, colors: Vec<SetColor>) -> Result<G::Gui, FError> {
 }
 
 
+// the page's gui as touchpage json.
+pub fn gui_json(gui: Gui, colors: Vec<SetColor>) -> Result<String, FError> {
+  let root = build_gui(gui, colors)?.to_root()?;
+  Ok(serde_json::to_string_pretty(
+    &touchpage::json::serialize_root(&root),
+  )?)
+}
+
+// a Label's touchpage name.
+fn label_name(name: &Option<String>, source: &Option<LabelSource>) -> String {
+  match (name, source) {
+    (Some(n), _) => n.clone(),
+    (None, Some(s)) => serde_lexpr::to_string(s).unwrap_or("".to_string()),
+    (None, None) => "".to_string(),
+  }
+}
+
+// the Labels that have sources.  the source has to find its label by
+// name, so unnamed ones are named here for their source and place among
+// the labels; two showing the same thing get a name each.  call this
+// before gui_json, so the gui has those names too.
+pub fn sourced_labels(control: &mut Control) -> Vec<SourcedLabel> {
+  let mut labels = Vec::new();
+  find_sourced_labels(control, &mut labels, &mut 0);
+  labels
+}
+
+fn find_sourced_labels(control: &mut Control, labels: &mut Vec<SourcedLabel>, count: &mut usize) {
+  match control {
+    Control::Label {
+      name,
+      label: _,
+      source,
+      interval,
+      proportion: _,
+    } => {
+      *count += 1;
+      if let Some(s) = &*source {
+        if name.is_none() {
+          *name = Some(format!("{} {}", label_name(name, source), count));
+        }
+        labels.push(SourcedLabel {
+          name: label_name(name, source),
+          source: s.clone(),
+          interval: *interval,
+        });
+      }
+    }
+    Control::Sizer { controls, .. } => {
+      for c in controls.iter_mut() {
+        find_sourced_labels(c, labels, count);
+      }
+    }
+    Control::Grid { controls, .. } => {
+      for c in controls.iter_mut() {
+        find_sourced_labels(&mut c.control, labels, count);
+      }
+    }
+    _ => (),
+  }
+}
+
+// true if any control sends MIDI, so the virtual port is only made when
+// it's wanted.
+pub fn uses_midi(control: &Control) -> bool {
+  let midi_action = |actions: &Vec<Action>| actions.iter().any(|a| matches!(a, Action::Midi(_)));
+  match control {
+    Control::Slider {
+      action: SliderAction::Midi { .. },
+      ..
+    } => true,
+    Control::Button { actions, .. } => midi_action(actions),
+    Control::Toggle { on, off, .. } => midi_action(on) || midi_action(off),
+    Control::MidiXy { .. } => true,
+    Control::Sizer { controls, .. } => controls.iter().any(uses_midi),
+    Control::Grid { controls, .. } => controls.iter().any(|c| uses_midi(&c.control)),
+    _ => false,
+  }
+}
@@ -0,0 +1,334 @@
+// ---------------------------------------------------------------
+// running Command actions.  each command runs on its own thread,
+// which waits for it with a timeout and shows the result in a label.
+// a command pressed again while it's running is ignored, killed and
+// restarted, or queued, as its policy says.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use touchpage::control_nexus::ControlNexus;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+// how long to wait for the rest of the output once a command exits;
+// anything it started in the background may keep the pipe open.
+const OUTPUT_WAIT: Duration = Duration::from_millis(500);
+
+#[derive(Clone)]
+pub struct Spec {
+  pub program: String,
+  pub args: Vec<String>,
+  pub shell: bool,
+  pub timeout: Option<Duration>,
+  pub env: BTreeMap<String, String>,
+  pub cwd: Option<String>,
+  pub policy: BL::CommandPolicy,
+  pub label: Option<String>,
+  pub show: Option<String>,
+}
+
+struct Running {
+  // changes when a command is killed and restarted, so the old thread
+  // knows it's been replaced.
+  generation: usize,
+  child: Arc<Mutex<Child>>,
+  queued: u32,
+}
+
+// commands by their prefs text, so each Command action is separate.
+#[derive(Clone)]
+pub struct Commands {
+  running: Arc<Mutex<HashMap<String, Running>>>,
+  generations: Arc<AtomicUsize>,
+}
+
+impl Commands {
+  pub fn new() -> Commands {
+    Commands {
+      running: Arc::new(Mutex::new(HashMap::new())),
+      generations: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Running>> {
+    match self.running.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  pub fn run(&self, key: String, spec: Spec, cn: &ControlNexus) {
+    let mut running = self.lock();
+    if let Some(r) = running.get_mut(&key) {
+      match spec.policy {
+        BL::CommandPolicy::Ignore => {
+          println!("{} is still running, ignored", spec.program);
+          return;
+        }
+        BL::CommandPolicy::Queue => {
+          r.queued += 1;
+          return;
+        }
+        BL::CommandPolicy::Kill => kill(&r.child),
+      }
+    }
+    let generation = self.generations.fetch_add(1, Ordering::SeqCst);
+    match start(&spec, cn) {
+      Some(child) => {
+        let child = Arc::new(Mutex::new(child));
+        running.insert(
+          key.clone(),
+          Running {
+            generation,
+            child: child.clone(),
+            queued: 0,
+          },
+        );
+        let commands = self.clone();
+        let cn = cn.clone();
+        thread::spawn(move || commands.wait(key, spec, generation, child, cn));
+      }
+      None => {
+        running.remove(&key);
+      }
+    }
+  }
+
+  // wait for the command, show how it went, then start it again if it
+  // was queued.
+  fn wait(
+    &self,
+    key: String,
+    spec: Spec,
+    generation: usize,
+    mut child: Arc<Mutex<Child>>,
+    cn: ControlNexus,
+  ) {
+    loop {
+      let output = take_output(&child);
+      let (status, timed_out) = wait_for(&child, spec.timeout);
+      let stdout = output.recv_timeout(OUTPUT_WAIT).unwrap_or_default();
+
+      let mut running = self.lock();
+      let replaced = match running.get(&key) {
+        Some(r) => r.generation != generation,
+        None => true,
+      };
+      let status = match status {
+        _ if timed_out => "timed out".to_string(),
+        _ if replaced => "killed".to_string(),
+        Ok(s) => match s.code() {
+          Some(c) => format!("exit {}", c),
+          None => "killed".to_string(),
+        },
+        Err(e) => format!("error: {}", e),
+      };
+      println!("{}: {}", spec.program, status);
+      // a replacement shows its own result.
+      if replaced {
+        return;
+      }
+      show(&spec, &cn, status.as_str(), stdout.as_str());
+
+      let next = match running.get_mut(&key) {
+        Some(r) if r.queued > 0 => {
+          r.queued -= 1;
+          start(&spec, &cn)
+        }
+        _ => None,
+      };
+      match next {
+        Some(c) => {
+          child = Arc::new(Mutex::new(c));
+          if let Some(r) = running.get_mut(&key) {
+            r.child = child.clone();
+          }
+        }
+        None => {
+          running.remove(&key);
+          return;
+        }
+      }
+    }
+  }
+}
+
+// run a command to the end, or until it's killed at the timeout.  the
+// status, like "exit 0" or "timed out", and the output.
+pub fn output(mut cmd: Command, timeout: Duration) -> (String, String) {
+  cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+  own_group(&mut cmd);
+  let child = match cmd.spawn() {
+    Ok(c) => Arc::new(Mutex::new(c)),
+    Err(e) => return (format!("error: {}", e), String::new()),
+  };
+  let output = take_output(&child);
+  let (status, timed_out) = wait_for(&child, Some(timeout));
+  let stdout = output.recv_timeout(OUTPUT_WAIT).unwrap_or_default();
+  let status = match status {
+    _ if timed_out => "timed out".to_string(),
+    Ok(s) => match s.code() {
+      Some(c) => format!("exit {}", c),
+      None => "killed".to_string(),
+    },
+    Err(e) => format!("error: {}", e),
+  };
+  (status, stdout)
+}
+
+// the exit status, and whether it had to be killed for taking too long.
+fn wait_for(
+  child: &Arc<Mutex<Child>>,
+  timeout: Option<Duration>,
+) -> (io::Result<ExitStatus>, bool) {
+  let started = Instant::now();
+  let mut timed_out = false;
+  let status = loop {
+    match lock_child(child).try_wait() {
+      Ok(Some(status)) => break Ok(status),
+      Ok(None) => (),
+      Err(e) => break Err(e),
+    }
+    match timeout {
+      Some(t) if !timed_out && started.elapsed() > t => {
+        kill(child);
+        timed_out = true;
+      }
+      _ => (),
+    }
+    thread::sleep(POLL_INTERVAL);
+  };
+  (status, timed_out)
+}
+
+fn lock_child(child: &Arc<Mutex<Child>>) -> std::sync::MutexGuard<'_, Child> {
+  match child.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+// the command's whole process group, so whatever a shell started goes
+// too.  no such group just means they're all gone already.
+#[cfg(unix)]
+pub fn kill(child: &Arc<Mutex<Child>>) {
+  let pid = lock_child(child).id() as libc::pid_t;
+  match unsafe { libc::kill(-pid, libc::SIGKILL) } {
+    0 => (),
+    _ => {
+      let e = std::io::Error::last_os_error();
+      match e.raw_os_error() {
+        Some(libc::ESRCH) => (),
+        _ => println!("error killing command: {}", e),
+      }
+    }
+  }
+}
+
+#[cfg(not(unix))]
+pub fn kill(child: &Arc<Mutex<Child>>) {
+  match lock_child(child).kill() {
+    Err(e) => println!("error killing command: {}", e),
+    _ => (),
+  }
+}
+
+fn start(spec: &Spec, cn: &ControlNexus) -> Option<Child> {
+  let mut cmd = if spec.shell {
+    shell_command(spec)
+  } else {
+    let mut c = Command::new(&spec.program);
+    c.args(&spec.args);
+    c
+  };
+  cmd.envs(&spec.env);
+  if let Some(d) = &spec.cwd {
+    cmd.current_dir(d);
+  }
+  cmd.stdin(Stdio::null()).stdout(Stdio::piped());
+  own_group(&mut cmd);
+  match cmd.spawn() {
+    Ok(child) => {
+      if let Some(l) = &spec.label {
+        cn.update_label(l.as_str(), "running...");
+      }
+      Some(child)
+    }
+    Err(e) => {
+      println!("error running {}: {}", spec.program, e);
+      show(spec, cn, format!("error: {}", e).as_str(), "");
+      None
+    }
+  }
+}
+
+// a group of its own, for kill().
+#[cfg(unix)]
+fn own_group(cmd: &mut Command) {
+  use std::os::unix::process::CommandExt;
+  cmd.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn own_group(_cmd: &mut Command) {}
+
+// the args become $1, $2 ... so they don't need quoting.
+#[cfg(not(target_os = "windows"))]
+fn shell_command(spec: &Spec) -> Command {
+  let mut c = Command::new("sh");
+  c.arg("-c")
+    .arg(&spec.program)
+    .arg("mousepage")
+    .args(&spec.args);
+  c
+}
+
+// cmd has no positional args, so they go on the end of the line.
+#[cfg(target_os = "windows")]
+fn shell_command(spec: &Spec) -> Command {
+  let mut c = Command::new("cmd");
+  c.arg("/C").arg(&spec.program).args(&spec.args);
+  c
+}
+
+// read stdout on another thread, so a chatty command doesn't fill the
+// pipe and stall.
+fn take_output(child: &Arc<Mutex<Child>>) -> mpsc::Receiver<String> {
+  let (tx, rx) = mpsc::channel();
+  match lock_child(child).stdout.take() {
+    Some(mut out) => {
+      thread::spawn(move || {
+        let mut s = String::new();
+        let _ = out.read_to_string(&mut s);
+        let _ = tx.send(s);
+      });
+    }
+    None => {
+      let _ = tx.send(String::new());
+    }
+  }
+  rx
+}
+
+// by default just the output if all went well, else the status too.
+fn show(spec: &Spec, cn: &ControlNexus, status: &str, stdout: &str) {
+  let label = match &spec.label {
+    Some(l) => l,
+    None => return,
+  };
+  let stdout = stdout.trim();
+  let text = match &spec.show {
+    Some(f) => f.replace("{status}", status).replace("{stdout}", stdout),
+    None if status == "exit 0" => stdout.to_string(),
+    None if stdout.is_empty() => status.to_string(),
+    None => format!("{}: {}", status, stdout),
+  };
+  cn.update_label(label.as_str(), text.as_str());
+}
@@ -0,0 +1,328 @@
+// ---------------------------------------------------------------
+// includes and templates in the prefs file, expanded into plain
+// controls before the settings are deserialized.
+//
+//   (Include (file . "mouse-buttons.scm"))
+//
+// is replaced by the control in that file, which is relative to the
+// including file.  templates are defined at the top level:
+//
+//   (templates ((name . "seek")
+//               (params label keys)
+//               (control Key (label $label) (keys . $keys) (proportion))))
+//
+// and used in place of a control, with $label and $keys replaced:
+//
+//   (Use (template . "seek") (args (label . "<<") (keys LControlKey LeftKey)))
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use failure::err_msg;
+use failure::Error as FError;
+use serde_lexpr::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+struct Template {
+  params: Vec<String>,
+  control: Value,
+}
+
+struct Expander {
+  templates: HashMap<String, Template>,
+  // files being included, outermost first.
+  files: Vec<PathBuf>,
+  // templates being used, outermost first.
+  using: Vec<String>,
+}
+
+// parse and expand a prefs file's contents.  filename is for finding
+// included files and for error messages.  also returns the paths of any
+// fields that were ignored.
+pub fn settings_from_str(s: &str, filename: &str) -> Result<(BL::Settings, Vec<String>), FError> {
+  let v = serde_lexpr::parse::from_str(s).map_err(|e| err_msg(format!("{}: {}", filename, e)))?;
+  let mut ex = Expander {
+    templates: HashMap::new(),
+    files: vec![PathBuf::from(filename)],
+    using: Vec::new(),
+  };
+
+  let (entries, _) = match v.as_cons() {
+    Some(c) => c.to_vec(),
+    None => return Err(err_msg(format!("{}: expected a list", filename))),
+  };
+  let mut rest = Vec::new();
+  for e in entries {
+    match e.as_cons().and_then(|c| c.car().as_symbol()) {
+      Some("templates") => ex.add_templates(e.as_cons().unwrap().cdr())?,
+      _ => rest.push(e),
+    }
+  }
+  let mut expanded = Vec::new();
+  for e in rest {
+    expanded.push(ex.expand(&e)?);
+  }
+
+  let v = Value::list(expanded);
+  let settings: BL::Settings =
+    serde_lexpr::from_value(&v).map_err(|e| err_msg(format!("{}: {}", filename, e)))?;
+
+  // anything in the file that doesn't come back out when the settings are
+  // written again was ignored.
+  let mut unknown = Vec::new();
+  if let Ok(written) = serde_lexpr::to_value(&settings) {
+    unknown_fields(&v, &written, "", &mut unknown);
+  }
+  Ok((settings, unknown))
+}
+
+// a list of (key . value) pairs, like a struct.
+fn is_alist(v: &Value) -> bool {
+  match elements(v) {
+    Some(es) => {
+      !es.is_empty()
+        && es
+          .iter()
+          .all(|e| e.as_cons().map(|c| c.car().is_symbol()).unwrap_or(false))
+    }
+    None => false,
+  }
+}
+
+// collect paths of keys in 'read' that aren't in 'written'.
+fn unknown_fields(read: &Value, written: &Value, path: &str, unknown: &mut Vec<String>) {
+  let (rc, wc) = match (read.as_cons(), written.as_cons()) {
+    (Some(r), Some(w)) => (r, w),
+    _ => return,
+  };
+  if is_alist(read) {
+    // match up keys in order, so lists of enums like (Key ..) (Key ..)
+    // pair off one to one.
+    let mut ws: Vec<Option<&Value>> = match elements(written) {
+      Some(es) => es.into_iter().map(Some).collect(),
+      None => Vec::new(),
+    };
+    for r in elements(read).unwrap_or_default() {
+      let rp = r.as_cons().unwrap();
+      let key = rp.car().as_symbol().unwrap_or("");
+      let subpath = if path.is_empty() {
+        key.to_string()
+      } else {
+        format!("{}.{}", path, key)
+      };
+      let found = ws.iter_mut().find(|w| {
+        w.and_then(|w| w.as_cons())
+          .map(|wp| wp.car().as_symbol() == Some(key))
+          .unwrap_or(false)
+      });
+      match found {
+        Some(w) => {
+          let wp = w.take().unwrap().as_cons().unwrap();
+          unknown_fields(rp.cdr(), wp.cdr(), subpath.as_str(), unknown);
+        }
+        None => unknown.push(subpath),
+      }
+    }
+  } else if rc.car().is_symbol() && rc.car() == wc.car() {
+    // enum variant; (Sizer (orientation . Vertical) ...)
+    unknown_fields(rc.cdr(), wc.cdr(), path, unknown);
+  } else {
+    let rs = elements(read).unwrap_or_default();
+    let ws = elements(written).unwrap_or_default();
+    for (i, (r, w)) in rs.iter().zip(ws.iter()).enumerate() {
+      unknown_fields(r, w, format!("{}[{}]", path, i).as_str(), unknown);
+    }
+  }
+}
+
+// the elements of a list, or None if it's not one.
+fn elements(v: &Value) -> Option<Vec<&Value>> {
+  match v.as_cons() {
+    Some(c) => Some(c.to_ref_vec().0),
+    None if v.is_nil() || v.is_null() => Some(Vec::new()),
+    None => None,
+  }
+}
+
+// the value of (key . value) in a list of pairs.
+fn alist_get<'a>(v: &'a Value, key: &str) -> Option<&'a Value> {
+  elements(v)?
+    .into_iter()
+    .filter_map(|e| e.as_cons())
+    .find(|c| c.car().as_symbol() == Some(key))
+    .map(|c| c.cdr())
+}
+
+// a name given as a string or symbol.
+fn name_of(v: &Value) -> Option<&str> {
+  v.as_str().or_else(|| v.as_symbol())
+}
+
+impl Expander {
+  fn current(&self) -> String {
+    match self.files.last() {
+      Some(f) => f.display().to_string(),
+      None => String::new(),
+    }
+  }
+
+  fn error(&self, msg: String) -> FError {
+    err_msg(format!("{}: {}", self.current(), msg))
+  }
+
+  fn add_templates(&mut self, defs: &Value) -> Result<(), FError> {
+    let defs = match elements(defs) {
+      Some(i) => i,
+      None => return Err(self.error("templates should be a list".to_string())),
+    };
+    for d in defs {
+      let name = match alist_get(d, "name").and_then(name_of) {
+        Some(n) => n.to_string(),
+        None => return Err(self.error(format!("template without a name: {}", d))),
+      };
+      let params = match alist_get(d, "params").and_then(elements) {
+        Some(ps) => ps
+          .into_iter()
+          .map(|p| name_of(p).map(|s| s.to_string()))
+          .collect::<Option<Vec<String>>>(),
+        None => Some(Vec::new()),
+      };
+      let params = match params {
+        Some(ps) => ps,
+        None => return Err(self.error(format!("template \"{}\": bad params", name))),
+      };
+      let control = match alist_get(d, "control") {
+        Some(c) => c.clone(),
+        None => return Err(self.error(format!("template \"{}\" has no control", name))),
+      };
+      self.templates.insert(name, Template { params, control });
+    }
+    Ok(())
+  }
+
+  // expand an element of a list, which might be an Include or Use.
+  fn expand(&mut self, v: &Value) -> Result<Value, FError> {
+    let c = match v.as_cons() {
+      Some(c) => c,
+      None => return Ok(v.clone()),
+    };
+    match c.car().as_symbol() {
+      Some("Include") => self.include(c.cdr()),
+      Some("Use") => self.use_template(c.cdr()),
+      // (control Sizer ...) has the control in the cdr.
+      Some("control") => Ok(Value::cons(c.car().clone(), self.expand(c.cdr())?)),
+      _ => Ok(Value::cons(
+        self.expand(c.car())?,
+        self.expand_tail(c.cdr())?,
+      )),
+    }
+  }
+
+  fn expand_tail(&mut self, v: &Value) -> Result<Value, FError> {
+    match v.as_cons() {
+      Some(c) => Ok(Value::cons(
+        self.expand(c.car())?,
+        self.expand_tail(c.cdr())?,
+      )),
+      None => Ok(v.clone()),
+    }
+  }
+
+  fn include(&mut self, args: &Value) -> Result<Value, FError> {
+    let file = match alist_get(args, "file").and_then(|f| f.as_str()) {
+      Some(f) => f,
+      None => return Err(self.error("Include needs (file . \"name.scm\")".to_string())),
+    };
+    let dir = match self.files.last().and_then(|f| f.parent()) {
+      Some(d) => d.to_path_buf(),
+      None => PathBuf::new(),
+    };
+    let path = dir.join(file);
+    let canonical = fs::canonicalize(&path)
+      .map_err(|e| self.error(format!("can't include \"{}\": {}", path.display(), e)))?;
+    if self
+      .files
+      .iter()
+      .any(|f| fs::canonicalize(f).ok().as_ref() == Some(&canonical))
+    {
+      let chain: Vec<String> = self.files.iter().map(|f| f.display().to_string()).collect();
+      return Err(self.error(format!(
+        "include cycle: {} -> {}",
+        chain.join(" -> "),
+        path.display()
+      )));
+    }
+    let s = fs::read_to_string(&path)
+      .map_err(|e| self.error(format!("can't include \"{}\": {}", path.display(), e)))?;
+    let v = serde_lexpr::parse::from_str(s.as_str()).map_err(|e| {
+      self.error(format!(
+        "error in included file \"{}\": {}",
+        path.display(),
+        e
+      ))
+    })?;
+    self.files.push(path);
+    let result = self.expand(&v);
+    self.files.pop();
+    result
+  }
+
+  fn use_template(&mut self, args: &Value) -> Result<Value, FError> {
+    let name = match alist_get(args, "template").and_then(name_of) {
+      Some(n) => n.to_string(),
+      None => return Err(self.error("Use needs (template . \"name\")".to_string())),
+    };
+    if self.using.contains(&name) {
+      return Err(self.error(format!(
+        "template \"{}\" uses itself: {} -> {}",
+        name,
+        self.using.join(" -> "),
+        name
+      )));
+    }
+    let (params, control) = match self.templates.get(&name) {
+      Some(t) => (t.params.clone(), t.control.clone()),
+      None => return Err(self.error(format!("no template named \"{}\"", name))),
+    };
+
+    let mut values = HashMap::new();
+    if let Some(given) = alist_get(args, "args").and_then(elements) {
+      for a in given {
+        match a
+          .as_cons()
+          .and_then(|c| c.car().as_symbol().map(|s| (s, c.cdr())))
+        {
+          Some((p, v)) => {
+            if !params.iter().any(|pp| pp == p) {
+              return Err(self.error(format!("template \"{}\" has no param \"{}\"", name, p)));
+            }
+            values.insert(format!("${}", p), v.clone());
+          }
+          None => return Err(self.error(format!("template \"{}\": bad arg {}", name, a))),
+        }
+      }
+    }
+    for p in params.iter() {
+      if !values.contains_key(&format!("${}", p)) {
+        return Err(self.error(format!("template \"{}\" needs arg \"{}\"", name, p)));
+      }
+    }
+
+    self.using.push(name);
+    let result = self.expand(&substitute(&control, &values));
+    self.using.pop();
+    result
+  }
+}
+
+// replace $param symbols anywhere in v.
+fn substitute(v: &Value, values: &HashMap<String, Value>) -> Value {
+  match v {
+    Value::Symbol(s) => match values.get(&s.to_string()) {
+      Some(r) => r.clone(),
+      None => v.clone(),
+    },
+    Value::Cons(c) => Value::cons(substitute(c.car(), values), substitute(c.cdr(), values)),
+    _ => v.clone(),
+  }
+}
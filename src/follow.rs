@@ -0,0 +1,194 @@
+// ---------------------------------------------------------------
+// switching profiles to follow the window with the focus, so the page
+// matches whatever program is in front.  the first rule whose regex
+// matches the window's class picks the profile.  only a change of
+// window switches, so a profile picked through the api stays until the
+// focus moves to another program.  with no rule matching the page stays
+// as it is; a last rule with an empty class catches everything else.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::profiles;
+use crate::window;
+use failure::err_msg;
+use failure::Error as FError;
+use regex::Regex;
+use std::thread;
+use std::time::Duration;
+
+const INTERVAL: Duration = Duration::from_millis(500);
+
+// which profile goes with the focused window, and when it's time to
+// switch.
+struct Follower {
+  rules: Vec<(Regex, String)>,
+  last_class: Option<String>,
+  last_error: Option<String>,
+}
+
+impl Follower {
+  fn new(rules: &[BL::WindowRule]) -> Result<Follower, FError> {
+    let mut compiled = Vec::new();
+    for r in rules {
+      match Regex::new(r.class.as_str()) {
+        Ok(re) => compiled.push((re, r.profile.clone())),
+        Err(e) => return Err(err_msg(format!("bad class regex {}: {}", r.class, e))),
+      }
+    }
+    Ok(Follower {
+      rules: compiled,
+      last_class: None,
+      last_error: None,
+    })
+  }
+
+  // the first rule's profile for this class.
+  fn profile_for(&self, class: &str) -> Option<&str> {
+    self
+      .rules
+      .iter()
+      .find(|(re, _)| re.is_match(class))
+      .map(|(_, profile)| profile.as_str())
+  }
+
+  // the profile to switch to, if the focus has moved to another program
+  // since last time.
+  fn next(&mut self, provider: &mut dyn window::Provider) -> Option<String> {
+    let class = match provider.active() {
+      Ok(w) => w.map(|w| w.class).unwrap_or_default(),
+      Err(e) => {
+        // just once, not every time round.
+        let e = e.to_string();
+        if self.last_error.as_ref() != Some(&e) {
+          println!("error finding the active window: {}", e);
+          self.last_error = Some(e);
+        }
+        return None;
+      }
+    };
+    self.last_error = None;
+    if self.last_class.as_ref() == Some(&class) {
+      return None;
+    }
+    let profile = self.profile_for(class.as_str()).map(|p| p.to_string());
+    self.last_class = Some(class);
+    profile
+  }
+}
+
+pub fn start(
+  rules: &[BL::WindowRule],
+  provider: window::SharedProvider,
+  profiles: profiles::SharedProfiles,
+) -> Result<(), FError> {
+  let mut follower = Follower::new(rules)?;
+  thread::spawn(move || loop {
+    thread::sleep(INTERVAL);
+    let next = follower.next(window::lock(&provider).as_mut());
+    if let Some(profile) = next {
+      match profiles::lock(&profiles).switch(profile.as_str()) {
+        Ok(()) => (),
+        Err(e) => println!("error switching to profile {}: {}", profile, e),
+      }
+    }
+  });
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::window::{ActiveWindow, Provider};
+
+  // whatever window the test says has the focus.
+  struct FakeProvider {
+    class: Option<&'static str>,
+    fail: bool,
+  }
+
+  impl Provider for FakeProvider {
+    fn active(&mut self) -> Result<Option<ActiveWindow>, FError> {
+      if self.fail {
+        return Err(err_msg("no display"));
+      }
+      Ok(self.class.map(|c| ActiveWindow {
+        title: format!("{} window", c),
+        class: c.to_string(),
+      }))
+    }
+  }
+
+  fn rules(rs: &[(&str, &str)]) -> Vec<BL::WindowRule> {
+    rs.iter()
+      .map(|(class, profile)| BL::WindowRule {
+        class: class.to_string(),
+        profile: profile.to_string(),
+      })
+      .collect()
+  }
+
+  fn fake(class: Option<&'static str>) -> FakeProvider {
+    FakeProvider { class, fail: false }
+  }
+
+  #[test]
+  fn first_matching_rule_wins() {
+    let f = Follower::new(&rules(&[
+      ("(?i)audacity", "audio"),
+      ("^Audacity$", "other"),
+    ]))
+    .unwrap();
+    assert_eq!(f.profile_for("Audacity"), Some("audio"));
+    assert_eq!(f.profile_for("vlc"), None);
+  }
+
+  #[test]
+  fn empty_class_catches_everything() {
+    let f = Follower::new(&rules(&[("^vlc$", "vlc"), ("", "main")])).unwrap();
+    assert_eq!(f.profile_for("vlc"), Some("vlc"));
+    assert_eq!(f.profile_for("firefox"), Some("main"));
+    assert_eq!(f.profile_for(""), Some("main"));
+  }
+
+  #[test]
+  fn bad_regex_is_an_error() {
+    assert!(Follower::new(&rules(&[("(vlc", "vlc")])).is_err());
+  }
+
+  #[test]
+  fn switches_only_when_the_focus_moves() {
+    let mut f = Follower::new(&rules(&[("^vlc$", "vlc"), ("^firefox$", "main")])).unwrap();
+    let mut p = fake(Some("vlc"));
+    assert_eq!(f.next(&mut p), Some("vlc".to_string()));
+    // still vlc; a profile picked some other way in the meantime stays.
+    assert_eq!(f.next(&mut p), None);
+    p.class = Some("firefox");
+    assert_eq!(f.next(&mut p), Some("main".to_string()));
+    p.class = Some("vlc");
+    assert_eq!(f.next(&mut p), Some("vlc".to_string()));
+  }
+
+  #[test]
+  fn no_rule_leaves_the_page_alone() {
+    let mut f = Follower::new(&rules(&[("^vlc$", "vlc")])).unwrap();
+    let mut p = fake(Some("vlc"));
+    assert_eq!(f.next(&mut p), Some("vlc".to_string()));
+    p.class = Some("xterm");
+    assert_eq!(f.next(&mut p), None);
+    p.class = None;
+    assert_eq!(f.next(&mut p), None);
+    // back to vlc from elsewhere switches again.
+    p.class = Some("vlc");
+    assert_eq!(f.next(&mut p), Some("vlc".to_string()));
+  }
+
+  #[test]
+  fn errors_dont_count_as_a_change() {
+    let mut f = Follower::new(&rules(&[("^vlc$", "vlc")])).unwrap();
+    let mut p = fake(Some("vlc"));
+    assert_eq!(f.next(&mut p), Some("vlc".to_string()));
+    p.fail = true;
+    assert_eq!(f.next(&mut p), None);
+    p.fail = false;
+    assert_eq!(f.next(&mut p), None);
+  }
+}
@@ -0,0 +1,99 @@
+// ---------------------------------------------------------------
+// prefs files in s-expression, JSON, TOML or YAML.  the format
+// comes from the file extension, or failing that a look at the
+// contents.  includes and templates are s-expression only.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::expand;
+use crate::pretty;
+use failure::err_msg;
+use failure::Error as FError;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+  Sexp,
+  Json,
+  Toml,
+  Yaml,
+}
+
+pub fn from_extension(filename: &str) -> Option<Format> {
+  let ext = Path::new(filename)
+    .extension()
+    .and_then(|e| e.to_str())
+    .map(|e| e.to_lowercase());
+  match ext.as_deref() {
+    Some("scm") | Some("sexp") | Some("lisp") | Some("el") => Some(Format::Sexp),
+    Some("json") => Some(Format::Json),
+    Some("toml") => Some(Format::Toml),
+    Some("yaml") | Some("yml") => Some(Format::Yaml),
+    _ => None,
+  }
+}
+
+// guess from the first non-blank, non-comment text.
+fn from_contents(s: &str) -> Format {
+  let first = s
+    .lines()
+    .map(|l| l.trim())
+    .find(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(';'));
+  match first {
+    Some(l) if l.starts_with('(') => Format::Sexp,
+    Some(l) if l.starts_with('{') => Format::Json,
+    Some(l) if l.starts_with('[') || l.contains(" = ") => Format::Toml,
+    _ => Format::Yaml,
+  }
+}
+
+pub fn detect(filename: &str, contents: &str) -> Format {
+  from_extension(filename).unwrap_or_else(|| from_contents(contents))
+}
+
+// unknown fields are ignored, with a warning, so a misspelled pref
+// doesn't go unnoticed.
+pub fn parse_settings(s: &str, filename: &str) -> Result<BL::Settings, FError> {
+  let mut unknown = Vec::new();
+  let mut note = |path: serde_ignored::Path| unknown.push(path.to_string());
+  let result = match detect(filename, s) {
+    Format::Sexp => {
+      let (settings, unknown) = expand::settings_from_str(s, filename)?;
+      warn_unknown(filename, &unknown);
+      return Ok(settings);
+    }
+    Format::Json => {
+      serde_ignored::deserialize(&mut serde_json::Deserializer::from_str(s), &mut note)
+        .map_err(FError::from)
+    }
+    Format::Toml => {
+      serde_ignored::deserialize(toml::Deserializer::new(s), &mut note).map_err(FError::from)
+    }
+    Format::Yaml => serde_ignored::deserialize(serde_yaml::Deserializer::from_str(s), &mut note)
+      .map_err(FError::from),
+  };
+  let settings = result.map_err(|e| err_msg(format!("{}: {}", filename, e)))?;
+  warn_unknown(filename, &unknown);
+  Ok(settings)
+}
+
+fn warn_unknown(filename: &str, unknown: &Vec<String>) {
+  for u in unknown {
+    println!("warning: {}: unknown field {}, ignored", filename, u);
+  }
+}
+
+// comments only apply to s-expressions.
+pub fn write_settings(
+  settings: &BL::Settings,
+  format: Format,
+  comments: bool,
+) -> Result<String, FError> {
+  match format {
+    Format::Sexp => pretty::to_string(settings, comments),
+    Format::Json => Ok(serde_json::to_string_pretty(settings)?),
+    // going through a toml Value puts plain values ahead of tables, which
+    // toml needs.
+    Format::Toml => Ok(toml::to_string_pretty(&toml::Value::try_from(settings)?)?),
+    Format::Yaml => Ok(serde_yaml::to_string(settings)?),
+  }
+}
@@ -0,0 +1,213 @@
+// ---------------------------------------------------------------
+// Grid control.  children are placed row by row like text, each
+// taking row_span x column_span cells, then the grid is cut into
+// nested touchpage sizers along lines no child crosses.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use failure::err_msg;
+use failure::Error as FError;
+use touchpage::controls as C;
+use touchpage::guibuilder as G;
+
+// a child's cells: rows r0..r1, columns c0..c1.
+#[derive(Clone, Copy)]
+struct Area {
+  r0: usize,
+  r1: usize,
+  c0: usize,
+  c1: usize,
+}
+
+struct Placed<'a> {
+  area: Area,
+  control: &'a BL::Control,
+}
+
+// find cells for the children, in order, leftmost topmost first.
+fn place(rows: usize, columns: usize, cells: &[BL::GridCell]) -> Result<Vec<Placed<'_>>, FError> {
+  let mut taken = vec![vec![false; columns]; rows];
+  let mut placed = Vec::new();
+  let mut next = 0;
+  for (i, cell) in cells.iter().enumerate() {
+    let rs = cell.row_span.unwrap_or(1).max(1) as usize;
+    let cs = cell.column_span.unwrap_or(1).max(1) as usize;
+    if cs > columns {
+      return Err(err_msg(format!(
+        "grid child {} spans {} columns but the grid has {}",
+        i + 1,
+        cs,
+        columns
+      )));
+    }
+    let fits = |r: usize, c: usize| {
+      r + rs <= rows
+        && c + cs <= columns
+        && (r..r + rs).all(|rr| (c..c + cs).all(|cc| !taken[rr][cc]))
+    };
+    let spot = (next..rows * columns)
+      .map(|n| (n / columns, n % columns))
+      .find(|(r, c)| fits(*r, *c));
+    match spot {
+      Some((r, c)) => {
+        for row in taken[r..r + rs].iter_mut() {
+          row[c..c + cs].fill(true);
+        }
+        next = r * columns + c + cs;
+        placed.push(Placed {
+          area: Area {
+            r0: r,
+            r1: r + rs,
+            c0: c,
+            c1: c + cs,
+          },
+          control: &cell.control,
+        });
+      }
+      None => {
+        return Err(err_msg(format!(
+          "grid child {} doesn't fit in the {}x{} grid",
+          i + 1,
+          rows,
+          columns
+        )))
+      }
+    }
+  }
+  Ok(placed)
+}
+
+// weights as given, or all 1.0.
+fn weights(given: &Option<Vec<f32>>, count: usize, what: &str) -> Result<Vec<f32>, FError> {
+  match given {
+    Some(w) => {
+      if w.len() != count {
+        Err(err_msg(format!(
+          "grid has {} {} but {} weights",
+          count,
+          what,
+          w.len()
+        )))
+      } else if w.iter().any(|x| x.is_nan() || *x < 0.0) {
+        Err(err_msg(format!("grid {} weights can't be negative", what)))
+      } else {
+        Ok(w.clone())
+      }
+    }
+    None => Ok(vec![1.0; count]),
+  }
+}
+
+// positions between lo and hi where no child in the area crosses, plus
+// the ends.
+fn cuts(placed: &[&Placed], lo: usize, hi: usize, span: fn(&Area) -> (usize, usize)) -> Vec<usize> {
+  let mut cs = vec![lo];
+  for k in lo + 1..hi {
+    if placed.iter().all(|p| {
+      let (a, b) = span(&p.area);
+      k <= a || k >= b
+    }) {
+      cs.push(k);
+    }
+  }
+  cs.push(hi);
+  cs
+}
+
+fn add_area<'a, 'b>(
+  gui: &'a mut G::Gui,
+  area: Area,
+  placed: Vec<&Placed<'b>>,
+  row_weights: &[f32],
+  column_weights: &[f32],
+) -> Result<&'a mut G::Gui, FError> {
+  if placed.is_empty() {
+    // nothing here; fill the space.
+    return gui.add_label("".to_string(), "".to_string());
+  }
+  if placed.len() == 1 {
+    let a = placed[0].area;
+    if a.r0 == area.r0 && a.r1 == area.r1 && a.c0 == area.c0 && a.c1 == area.c1 {
+      return BL::add_control(gui, placed[0].control);
+    }
+  }
+
+  // cut into bands of rows if possible, otherwise columns.
+  let rowcuts = cuts(&placed, area.r0, area.r1, |a| (a.r0, a.r1));
+  let colcuts = cuts(&placed, area.c0, area.c1, |a| (a.c0, a.c1));
+  let (orientation, bands, ws) = if rowcuts.len() > 2 {
+    (C::Orientation::Vertical, rowcuts, row_weights)
+  } else if colcuts.len() > 2 {
+    (C::Orientation::Horizontal, colcuts, column_weights)
+  } else {
+    return Err(err_msg(
+      "grid spans overlap in a way that can't be split into rows and columns",
+    ));
+  };
+
+  // bands weighted 0 all round share the space by how many cells they
+  // take, rather than dividing by 0.
+  let total: f32 = ws[bands[0]..bands[bands.len() - 1]].iter().sum();
+  let props = bands
+    .windows(2)
+    .map(|b| {
+      if total > 0.0 {
+        ws[b[0]..b[1]].iter().sum::<f32>() / total
+      } else {
+        (b[1] - b[0]) as f32 / (bands[bands.len() - 1] - bands[0]) as f32
+      }
+    })
+    .collect();
+  let mut g = gui.add_sizer(orientation, Some(props))?;
+  for b in bands.windows(2) {
+    let sub = match orientation {
+      C::Orientation::Vertical => Area {
+        r0: b[0],
+        r1: b[1],
+        ..area
+      },
+      C::Orientation::Horizontal => Area {
+        c0: b[0],
+        c1: b[1],
+        ..area
+      },
+    };
+    let inside = placed
+      .iter()
+      .filter(|p| {
+        p.area.r0 >= sub.r0 && p.area.r1 <= sub.r1 && p.area.c0 >= sub.c0 && p.area.c1 <= sub.c1
+      })
+      .cloned()
+      .collect();
+    g = add_area(g, sub, inside, row_weights, column_weights)?;
+  }
+  g.end_sizer()
+}
+
+pub fn add_grid<'a>(
+  gui: &'a mut G::Gui,
+  rows: u32,
+  columns: u32,
+  row_weights: &Option<Vec<f32>>,
+  column_weights: &Option<Vec<f32>>,
+  cells: &[BL::GridCell],
+) -> Result<&'a mut G::Gui, FError> {
+  let (rows, columns) = (rows as usize, columns as usize);
+  if rows == 0 || columns == 0 {
+    return Err(err_msg("grid needs at least one row and column"));
+  }
+  let rw = weights(row_weights, rows, "rows")?;
+  let cw = weights(column_weights, columns, "columns")?;
+  let placed = place(rows, columns, cells)?;
+  add_area(
+    gui,
+    Area {
+      r0: 0,
+      r1: rows,
+      c0: 0,
+      c1: columns,
+    },
+    placed.iter().collect(),
+    &rw,
+    &cw,
+  )
+}
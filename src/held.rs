@@ -0,0 +1,186 @@
+// ---------------------------------------------------------------
+// keys and mouse buttons currently held down on the host, and MIDI
+// notes sounding.  if the phone goes away mid-press the release never
+// arrives, so the ones a client pressed get released when it
+// disconnects, and everything can be released when things go quiet for
+// too long, if release_timeout is set, or from a 'release all' button.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::midi;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// which page's message is being handled, so things it holds can be
+// released when it goes.  0 for the api and such.
+pub type ClientId = usize;
+
+pub struct Held {
+  // the client whose message is being handled now.
+  pub client: ClientId,
+  // what's held, and which client pressed it.
+  keys: Vec<(BL::KeybdKey, ClientId)>,
+  buttons: Vec<(BL::MouseButton, ClientId)>,
+  // the scroll button is held, so the mousexy area scrolls.
+  pub scroll_mode: bool,
+  scroll_client: ClientId,
+  // cursor speed in pixels per second, per mouse Joystick control.
+  cursor_velocity: HashMap<Vec<i32>, ((f32, f32), ClientId)>,
+  // Midi actions pressed and not released; notes on, and ccs at their
+  // 'on' value.
+  midi_held: Vec<(BL::MidiMessage, ClientId)>,
+  midi: midi::Midi,
+  last_activity: Instant,
+}
+
+pub type SharedHeld = Arc<Mutex<Held>>;
+
+pub fn new_shared(midi: midi::Midi) -> SharedHeld {
+  Arc::new(Mutex::new(Held {
+    client: 0,
+    keys: Vec::new(),
+    buttons: Vec::new(),
+    scroll_mode: false,
+    scroll_client: 0,
+    cursor_velocity: HashMap::new(),
+    midi_held: Vec::new(),
+    midi,
+    last_activity: Instant::now(),
+  }))
+}
+
+pub fn lock(held: &SharedHeld) -> MutexGuard<'_, Held> {
+  match held.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+impl Held {
+  // a message came in from a client.
+  pub fn touch(&mut self) {
+    self.last_activity = Instant::now();
+  }
+
+  pub fn anything_held(&self) -> bool {
+    !self.keys.is_empty()
+      || !self.buttons.is_empty()
+      || self.scroll_mode
+      || !self.cursor_velocity.is_empty()
+      || !self.midi_held.is_empty()
+  }
+
+  pub fn holds_key(&self, k: &BL::KeybdKey) -> bool {
+    self.keys.iter().any(|(hk, _)| hk == k)
+  }
+
+  pub fn press_key(&mut self, k: &BL::KeybdKey) {
+    BL::convert_keybdkey(k).press();
+    if !self.holds_key(k) {
+      self.keys.push((*k, self.client));
+    }
+  }
+
+  pub fn release_key(&mut self, k: &BL::KeybdKey) {
+    BL::convert_keybdkey(k).release();
+    self.keys.retain(|(hk, _)| hk != k);
+  }
+
+  pub fn press_button(&mut self, b: &BL::MouseButton) {
+    BL::convert_mousebutton(b).press();
+    if !self.buttons.iter().any(|(hb, _)| hb == b) {
+      self.buttons.push((*b, self.client));
+    }
+  }
+
+  pub fn release_button(&mut self, b: &BL::MouseButton) {
+    BL::convert_mousebutton(b).release();
+    self.buttons.retain(|(hb, _)| hb != b);
+  }
+
+  // a button's Midi action.
+  pub fn midi_button(&mut self, m: &BL::MidiMessage, pressed: bool) {
+    self.midi.button(m, pressed);
+    if pressed {
+      if !self.midi_held.iter().any(|(hm, _)| hm == m) {
+        self.midi_held.push((m.clone(), self.client));
+      }
+    } else {
+      self.midi_held.retain(|(hm, _)| hm != m);
+    }
+  }
+
+  pub fn set_scroll_mode(&mut self, on: bool) {
+    self.scroll_mode = on;
+    self.scroll_client = self.client;
+  }
+
+  // None to stop.
+  pub fn set_cursor_velocity(&mut self, control_id: &[i32], velocity: Option<(f32, f32)>) {
+    match velocity {
+      Some(v) => {
+        self
+          .cursor_velocity
+          .insert(control_id.to_vec(), (v, self.client));
+      }
+      None => {
+        self.cursor_velocity.remove(control_id);
+      }
+    }
+  }
+
+  // all the joysticks' pushes added up.
+  pub fn total_cursor_velocity(&self) -> (f32, f32) {
+    self
+      .cursor_velocity
+      .values()
+      .fold((0.0, 0.0), |(ax, ay), ((x, y), _)| (ax + x, ay + y))
+  }
+
+  // release everything, most recently pressed first.
+  pub fn release_all(&mut self) {
+    self.release(|_| true);
+  }
+
+  // release what this client is holding.
+  pub fn release_client(&mut self, client: ClientId) {
+    self.release(|c| c == client);
+  }
+
+  fn release<F: Fn(ClientId) -> bool>(&mut self, which: F) {
+    for (b, _) in self.buttons.iter().rev().filter(|(_, c)| which(*c)) {
+      BL::convert_mousebutton(b).release();
+    }
+    self.buttons.retain(|(_, c)| !which(*c));
+    for (k, _) in self.keys.iter().rev().filter(|(_, c)| which(*c)) {
+      BL::convert_keybdkey(k).release();
+    }
+    self.keys.retain(|(_, c)| !which(*c));
+    for (m, _) in self.midi_held.iter().rev().filter(|(_, c)| which(*c)) {
+      self.midi.button(m, false);
+    }
+    self.midi_held.retain(|(_, c)| !which(*c));
+    if which(self.scroll_client) {
+      self.scroll_mode = false;
+    }
+    self.cursor_velocity.retain(|_, (_, c)| !which(*c));
+  }
+}
+
+// release everything if nothing's been heard from the clients for
+// 'timeout' while keys or buttons are held.  a key held down on purpose
+// counts as nothing heard, so this is only on if release_timeout is set.
+pub fn start_idle_watch(held: SharedHeld, timeout: Duration) {
+  thread::spawn(move || loop {
+    thread::sleep(timeout / 4);
+    let mut h = lock(&held);
+    if h.anything_held() && h.last_activity.elapsed() > timeout {
+      println!(
+        "no activity for {} ms, releasing held keys, buttons and notes",
+        timeout.as_millis()
+      );
+      h.release_all();
+    }
+  });
+}
@@ -0,0 +1,144 @@
+// ---------------------------------------------------------------
+// Http actions; webhooks for home automation, OBS and such.  each
+// action's requests go out in order on a thread of its own, so a slow
+// server doesn't hold up the controls and a release can't overtake its
+// press.
+// ---------------------------------------------------------------
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use touchpage::control_nexus::ControlNexus;
+
+const DEFAULT_TIMEOUT: u64 = 5000;
+// only so much of a response is kept for showing in a label.
+const MAX_BODY: u64 = 4096;
+
+pub struct Request {
+  pub method: Option<String>,
+  pub url: String,
+  pub headers: BTreeMap<String, String>,
+  pub body: Option<String>,
+  pub timeout: Option<u64>,
+  pub label: Option<String>,
+  pub show: Option<String>,
+}
+
+type Job = (Request, bool, ControlNexus);
+
+// a worker per action, by its prefs text like commands.
+pub struct Http {
+  workers: HashMap<String, mpsc::Sender<Job>>,
+}
+
+impl Http {
+  pub fn new() -> Http {
+    Http {
+      workers: HashMap::new(),
+    }
+  }
+
+  pub fn send(&mut self, key: String, req: Request, pressed: bool, cn: &ControlNexus) {
+    let job = (req, pressed, cn.clone());
+    // a worker that died takes the job back, for a new one.
+    let job = match self.workers.get(&key) {
+      Some(tx) => match tx.send(job) {
+        Ok(()) => return,
+        Err(mpsc::SendError(job)) => job,
+      },
+      None => job,
+    };
+    let (tx, rx) = mpsc::channel::<Job>();
+    thread::spawn(move || {
+      for (req, pressed, cn) in rx {
+        send(&req, pressed, &cn);
+      }
+    });
+    let _ = tx.send(job);
+    self.workers.insert(key, tx);
+  }
+}
+
+// one request, and its result in the label.
+fn send(req: &Request, pressed: bool, cn: &ControlNexus) {
+  let event = if pressed { "press" } else { "release" };
+  let (status, body) = match request(req, event) {
+    Ok(r) => {
+      println!("{} {}: {}", method(req), req.url, r.0);
+      r
+    }
+    // the whole error is long for a label; just the kind goes there.
+    Err(e) => {
+      println!("{} {}: {}", method(req), req.url, e);
+      (format!("error: {}", e.kind()), String::new())
+    }
+  };
+  if let Some(l) = &req.label {
+    let body = body.trim();
+    let text = match &req.show {
+      Some(f) => f
+        .replace("{status}", status.as_str())
+        .replace("{body}", body),
+      None => status,
+    };
+    cn.update_label(l.as_str(), text.as_str());
+  }
+}
+
+// GET unless there's a body.
+fn method(req: &Request) -> String {
+  match (&req.method, &req.body) {
+    (Some(m), _) => m.to_uppercase(),
+    (None, Some(_)) => "POST".to_string(),
+    (None, None) => "GET".to_string(),
+  }
+}
+
+// status line and body.  error statuses count as a response.
+fn request(req: &Request, event: &str) -> Result<(String, String), Box<ureq::Error>> {
+  let agent = ureq::AgentBuilder::new()
+    .timeout(Duration::from_millis(
+      req.timeout.unwrap_or(DEFAULT_TIMEOUT),
+    ))
+    .build();
+  let mut r = agent.request(method(req).as_str(), fill(req.url.as_str(), event).as_str());
+  for (k, v) in req.headers.iter() {
+    r = r.set(k.as_str(), fill(v.as_str(), event).as_str());
+  }
+  let result = match &req.body {
+    Some(b) => r.send_string(fill(b.as_str(), event).as_str()),
+    None => r.call(),
+  };
+  let response = match result {
+    Ok(response) => response,
+    Err(ureq::Error::Status(_, response)) => response,
+    Err(e) => return Err(Box::new(e)),
+  };
+  let status = format!("{} {}", response.status(), response.status_text());
+  let mut body = String::new();
+  let _ = response
+    .into_reader()
+    .take(MAX_BODY)
+    .read_to_string(&mut body);
+  Ok((status, body))
+}
+
+// replace {event} and {env:NAME}.
+fn fill(s: &str, event: &str) -> String {
+  let mut out = String::new();
+  let mut rest = s.replace("{event}", event);
+  while let Some(i) = rest.find("{env:") {
+    match rest[i..].find('}') {
+      Some(j) => {
+        out.push_str(&rest[..i]);
+        out.push_str(env::var(&rest[i + 5..i + j]).unwrap_or_default().as_str());
+        rest = rest[i + j + 1..].to_string();
+      }
+      None => break,
+    }
+  }
+  out.push_str(rest.as_str());
+  out
+}
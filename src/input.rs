@@ -0,0 +1,55 @@
+// ---------------------------------------------------------------
+// one-shot input on the host, shared by the controls that map
+// movement onto scrolling, key presses or mouse motion.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+
+#[cfg(target_os = "linux")]
+use inputbot::{MouseButton, MouseCursor};
+#[cfg(target_os = "windows")]
+use inputbot::{MouseCursor, MouseWheel};
+
+// scroll 'ticks' clicks; positive is down or right.
+pub fn scroll(orientation: &BL::Orientation, ticks: i32) {
+  #[cfg(target_os = "linux")]
+  {
+    let b = match (orientation, ticks < 0) {
+      (BL::Orientation::Vertical, true) => 4,
+      (BL::Orientation::Vertical, false) => 5,
+      (BL::Orientation::Horizontal, true) => 6,
+      (BL::Orientation::Horizontal, false) => 7,
+    };
+    for _ in 0..ticks.abs() {
+      MouseButton::OtherButton(b).press();
+      MouseButton::OtherButton(b).release();
+    }
+  }
+
+  #[cfg(target_os = "windows")]
+  {
+    match orientation {
+      BL::Orientation::Vertical => MouseWheel.scroll_ver(ticks),
+      BL::Orientation::Horizontal => MouseWheel.scroll_hor(ticks),
+    }
+  }
+}
+
+pub fn move_mouse(orientation: &BL::Orientation, pixels: i32) {
+  match orientation {
+    BL::Orientation::Vertical => MouseCursor.move_rel(0, pixels),
+    BL::Orientation::Horizontal => MouseCursor.move_rel(pixels, 0),
+  }
+}
+
+// press and release a key chord 'times' times.  keys are pressed left to
+// right and released right to left, like the Key control.
+pub fn tap_keys(keys: &Vec<BL::KeybdKey>, times: u32) {
+  for _ in 0..times {
+    for k in keys {
+      BL::convert_keybdkey(k).press();
+    }
+    for k in keys.iter().rev() {
+      BL::convert_keybdkey(k).release();
+    }
+  }
+}
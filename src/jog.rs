@@ -0,0 +1,83 @@
+// ---------------------------------------------------------------
+// Jog control handling.  dragging around the center of an xy area
+// taps the clockwise or counter clockwise keys, steps_per_revolution
+// times per turn, so turning faster repeats faster.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::input;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use touchpage::control_updates as cu;
+
+// touches closer than this to the center don't have a useful angle.
+const DEAD_RADIUS: f32 = 0.1;
+
+struct JogTouch {
+  last_angle: Option<f32>,
+  // rotation since the last step, radians.  clockwise is positive.
+  turned: f32,
+}
+
+pub struct JogState {
+  touches: HashMap<Vec<i32>, JogTouch>,
+}
+
+impl JogState {
+  pub fn new() -> JogState {
+    JogState {
+      touches: HashMap::new(),
+    }
+  }
+
+  pub fn on_update(
+    &mut self,
+    clockwise: &Vec<BL::KeybdKey>,
+    counter_clockwise: &Vec<BL::KeybdKey>,
+    steps_per_revolution: u32,
+    control_id: &Vec<i32>,
+    state: &Option<cu::PressState>,
+    location: &Option<(f32, f32)>,
+  ) {
+    if let Some(cu::PressState::Unpressed) = state {
+      self.touches.remove(control_id);
+      return;
+    }
+
+    let (x, y) = match location {
+      Some(l) => *l,
+      None => return,
+    };
+    let (dx, dy) = (x - 0.5, y - 0.5);
+    let touch = self.touches.entry(control_id.clone()).or_insert(JogTouch {
+      last_angle: None,
+      turned: 0.0,
+    });
+    if (dx * dx + dy * dy).sqrt() < DEAD_RADIUS {
+      touch.last_angle = None;
+      return;
+    }
+
+    // y grows downwards on the page, so increasing angle is clockwise.
+    let angle = dy.atan2(dx);
+    if let Some(la) = touch.last_angle {
+      let mut d = angle - la;
+      if d > PI {
+        d -= 2.0 * PI;
+      } else if d < -PI {
+        d += 2.0 * PI;
+      }
+      touch.turned += d;
+      let step_angle = 2.0 * PI / steps_per_revolution.max(1) as f32;
+      let steps = (touch.turned / step_angle).trunc();
+      if steps != 0.0 {
+        touch.turned -= steps * step_angle;
+        if steps > 0.0 {
+          input::tap_keys(clockwise, steps as u32);
+        } else {
+          input::tap_keys(counter_clockwise, -steps as u32);
+        }
+      }
+    }
+    touch.last_angle = Some(angle);
+  }
+}
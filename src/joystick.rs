@@ -0,0 +1,161 @@
+// ---------------------------------------------------------------
+// Joystick control handling.  unlike MouseXy, displacement from the
+// center is held: the cursor keeps moving, or the direction keys stay
+// down, until the touch moves back to the center or lifts.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::held;
+use inputbot::MouseCursor;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::thread;
+use std::time::{Duration, Instant};
+use touchpage::control_updates as cu;
+
+// used when the config leaves deadzone empty.
+pub const DEFAULT_DEADZONE: f32 = 0.2;
+
+// how often the cursor gets moved while a mouse joystick is pushed.
+const MOVE_INTERVAL: Duration = Duration::from_millis(16);
+
+pub struct JoystickState {
+  held: held::SharedHeld,
+  // direction keys currently down, per Keys joystick.
+  keys: HashMap<Vec<i32>, Vec<BL::KeybdKey>>,
+  mover_started: bool,
+}
+
+impl JoystickState {
+  pub fn new(held: held::SharedHeld) -> JoystickState {
+    JoystickState {
+      held,
+      keys: HashMap::new(),
+      mover_started: false,
+    }
+  }
+
+  // the gui changed, and held already let go of the keys.
+  pub fn forget_keys(&mut self) {
+    self.keys.clear();
+  }
+
+  pub fn on_update(
+    &mut self,
+    action: &BL::JoystickAction,
+    deadzone: Option<f32>,
+    control_id: &Vec<i32>,
+    state: &Option<cu::PressState>,
+    location: &Option<(f32, f32)>,
+  ) {
+    // stick position, -1 to 1 on each axis.  None when lifted.
+    let stick = match (state, location) {
+      (Some(cu::PressState::Unpressed), _) => None,
+      (_, Some((x, y))) => Some((2.0 * x - 1.0, 2.0 * y - 1.0)),
+      (_, None) => return,
+    };
+    let deadzone = deadzone.unwrap_or(DEFAULT_DEADZONE);
+
+    match action {
+      BL::JoystickAction::Mouse { speed } => {
+        let mut h = held::lock(&self.held);
+        let velocity = stick
+          .and_then(|s| outside_deadzone(s, deadzone))
+          .map(|(dx, dy)| (dx * speed, dy * speed));
+        h.set_cursor_velocity(control_id, velocity);
+        drop(h);
+        if !self.mover_started {
+          start_mover(self.held.clone());
+          self.mover_started = true;
+        }
+      }
+      BL::JoystickAction::Keys {
+        up,
+        down,
+        left,
+        right,
+      } => {
+        let mut want: Vec<BL::KeybdKey> = Vec::new();
+        if let Some((dx, dy)) = stick.and_then(|s| outside_deadzone(s, deadzone)) {
+          // 8 directions, 45 degrees each, starting at right and
+          // going clockwise since y grows downwards.
+          let sector = ((dy.atan2(dx) / (PI / 4.0)).round() as i32).rem_euclid(8);
+          let (h, v) = match sector {
+            0 => (Some(right), None),
+            1 => (Some(right), Some(down)),
+            2 => (None, Some(down)),
+            3 => (Some(left), Some(down)),
+            4 => (Some(left), None),
+            5 => (Some(left), Some(up)),
+            6 => (None, Some(up)),
+            _ => (Some(right), Some(up)),
+          };
+          for keys in h.iter().chain(v.iter()) {
+            for k in keys.iter() {
+              if !want.contains(k) {
+                want.push(*k);
+              }
+            }
+          }
+        }
+        self.set_keys(control_id, want);
+      }
+    }
+  }
+
+  // release keys no longer wanted and press new ones.  keys that were
+  // released from elsewhere (release all, idle timeout) get pressed again.
+  fn set_keys(&mut self, control_id: &Vec<i32>, want: Vec<BL::KeybdKey>) {
+    let mut h = held::lock(&self.held);
+    let was = self.keys.remove(control_id).unwrap_or_default();
+    for k in was.iter().rev() {
+      if !want.contains(k) {
+        h.release_key(k);
+      }
+    }
+    for k in want.iter() {
+      if !h.holds_key(k) {
+        h.press_key(k);
+      }
+    }
+    if !want.is_empty() {
+      self.keys.insert(control_id.clone(), want);
+    }
+  }
+}
+
+// scale the part of the displacement outside the deadzone back to 0..1,
+// or None if inside it.
+fn outside_deadzone((dx, dy): (f32, f32), deadzone: f32) -> Option<(f32, f32)> {
+  let r = (dx * dx + dy * dy).sqrt();
+  if r <= deadzone || deadzone >= 1.0 {
+    None
+  } else {
+    let scale = ((r - deadzone) / (1.0 - deadzone)).min(1.0) / r;
+    Some((dx * scale, dy * scale))
+  }
+}
+
+// move the cursor by the summed joystick velocities, keeping the
+// fractional pixels so slow speeds still get somewhere.
+fn start_mover(held: held::SharedHeld) {
+  thread::spawn(move || {
+    let mut last = Instant::now();
+    let mut rem = (0.0, 0.0);
+    loop {
+      thread::sleep(MOVE_INTERVAL);
+      let secs = last.elapsed().as_secs_f32();
+      last = Instant::now();
+      let (vx, vy) = held::lock(&held).total_cursor_velocity();
+      if vx == 0.0 && vy == 0.0 {
+        rem = (0.0, 0.0);
+        continue;
+      }
+      let (mx, my) = (rem.0 + vx * secs, rem.1 + vy * secs);
+      let (px, py) = (mx.trunc(), my.trunc());
+      rem = (mx - px, my - py);
+      if px != 0.0 || py != 0.0 {
+        MouseCursor.move_rel(px as i32, py as i32);
+      }
+    }
+  });
+}
@@ -0,0 +1,561 @@
+// ---------------------------------------------------------------
+// Keyboard control.  expands a layout into rows of buttons when
+// the gui is built, and handles the shift key, which latches until
+// the next key and relabels the keys while it's on.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::buildlisp::KeybdKey as K;
+use crate::held;
+use failure::Error as FError;
+use touchpage::control_nexus::ControlNexus;
+use touchpage::control_updates as cu;
+use touchpage::controls as C;
+use touchpage::guibuilder as G;
+
+// one key on the generated keyboard.
+struct KeyCap {
+  label: String,
+  shift_label: String,
+  keys: Vec<K>,
+  // relative to a letter key.
+  width: f32,
+  is_shift: bool,
+}
+
+fn cap(label: &str, shift_label: &str, key: K) -> KeyCap {
+  KeyCap {
+    label: label.to_string(),
+    shift_label: shift_label.to_string(),
+    keys: vec![key],
+    width: 1.0,
+    is_shift: false,
+  }
+}
+
+fn wide(label: &str, key: K, width: f32) -> KeyCap {
+  KeyCap {
+    width,
+    ..cap(label, label, key)
+  }
+}
+
+fn shift(width: f32) -> KeyCap {
+  KeyCap {
+    is_shift: true,
+    ..wide("shift", K::LShiftKey, width)
+  }
+}
+
+// a row of single character keys; label, shift label and key for each.
+fn chars(keys: &[(&str, &str, K)]) -> Vec<KeyCap> {
+  keys.iter().map(|(l, s, k)| cap(l, s, *k)).collect()
+}
+
+// keys inputbot has no name for.  on linux OtherKey is an X keysym, on
+// windows a virtual key code.
+fn other(keysym: u64, _vk: u64) -> K {
+  #[cfg(target_os = "windows")]
+  {
+    K::OtherKey(_vk)
+  }
+  #[cfg(not(target_os = "windows"))]
+  {
+    K::OtherKey(keysym)
+  }
+}
+
+fn function_row() -> Vec<KeyCap> {
+  let fkeys = [
+    K::F1Key,
+    K::F2Key,
+    K::F3Key,
+    K::F4Key,
+    K::F5Key,
+    K::F6Key,
+    K::F7Key,
+    K::F8Key,
+    K::F9Key,
+    K::F10Key,
+    K::F11Key,
+    K::F12Key,
+  ];
+  let mut row = vec![wide("esc", K::EscapeKey, 1.0)];
+  for (i, k) in fkeys.iter().enumerate() {
+    row.push(wide(format!("F{}", i + 1).as_str(), *k, 1.0));
+  }
+  row
+}
+
+fn space_row() -> Vec<KeyCap> {
+  vec![
+    wide("ctrl", K::LControlKey, 1.5),
+    wide("alt", other(0xffe9, 0x12), 1.5),
+    wide("space", K::SpaceKey, 6.0),
+    wide("←", K::LeftKey, 1.0),
+    wide("↑", K::UpKey, 1.0),
+    wide("↓", K::DownKey, 1.0),
+    wide("→", K::RightKey, 1.0),
+  ]
+}
+
+fn qwerty_row(row: &BL::KeyboardRow) -> Vec<KeyCap> {
+  let mut keys = Vec::new();
+  match row {
+    BL::KeyboardRow::Function => return function_row(),
+    BL::KeyboardRow::Number => {
+      keys.extend(chars(&[
+        ("`", "~", other(0x60, 0xc0)),
+        ("1", "!", K::Numrow1Key),
+        ("2", "@", K::Numrow2Key),
+        ("3", "#", K::Numrow3Key),
+        ("4", "$", K::Numrow4Key),
+        ("5", "%", K::Numrow5Key),
+        ("6", "^", K::Numrow6Key),
+        ("7", "&", K::Numrow7Key),
+        ("8", "*", K::Numrow8Key),
+        ("9", "(", K::Numrow9Key),
+        ("0", ")", K::Numrow0Key),
+        ("-", "_", other(0x2d, 0xbd)),
+        ("=", "+", other(0x3d, 0xbb)),
+      ]));
+      keys.push(wide("bksp", K::BackspaceKey, 2.0));
+    }
+    BL::KeyboardRow::Top => {
+      keys.push(wide("tab", K::TabKey, 1.5));
+      keys.extend(chars(&[
+        ("q", "Q", K::QKey),
+        ("w", "W", K::WKey),
+        ("e", "E", K::EKey),
+        ("r", "R", K::RKey),
+        ("t", "T", K::TKey),
+        ("y", "Y", K::YKey),
+        ("u", "U", K::UKey),
+        ("i", "I", K::IKey),
+        ("o", "O", K::OKey),
+        ("p", "P", K::PKey),
+        ("[", "{", other(0x5b, 0xdb)),
+        ("]", "}", other(0x5d, 0xdd)),
+      ]));
+      keys.push(KeyCap {
+        width: 1.5,
+        ..cap("\\", "|", other(0x5c, 0xdc))
+      });
+    }
+    BL::KeyboardRow::Home => {
+      keys.push(wide("caps", K::CapsLockKey, 1.75));
+      keys.extend(chars(&[
+        ("a", "A", K::AKey),
+        ("s", "S", K::SKey),
+        ("d", "D", K::DKey),
+        ("f", "F", K::FKey),
+        ("g", "G", K::GKey),
+        ("h", "H", K::HKey),
+        ("j", "J", K::JKey),
+        ("k", "K", K::KKey),
+        ("l", "L", K::LKey),
+        (";", ":", other(0x3b, 0xba)),
+        ("'", "\"", other(0x27, 0xde)),
+      ]));
+      keys.push(wide("enter", K::EnterKey, 2.25));
+    }
+    BL::KeyboardRow::Bottom => {
+      keys.push(shift(2.25));
+      keys.extend(chars(&[
+        ("z", "Z", K::ZKey),
+        ("x", "X", K::XKey),
+        ("c", "C", K::CKey),
+        ("v", "V", K::VKey),
+        ("b", "B", K::BKey),
+        ("n", "N", K::NKey),
+        ("m", "M", K::MKey),
+        (",", "<", other(0x2c, 0xbc)),
+        (".", ">", other(0x2e, 0xbe)),
+        ("/", "?", other(0x2f, 0xbf)),
+      ]));
+      keys.push(shift(2.75));
+    }
+    BL::KeyboardRow::Space => return space_row(),
+  }
+  keys
+}
+
+// labels are for a host set to a french azerty layout.
+fn azerty_row(row: &BL::KeyboardRow) -> Vec<KeyCap> {
+  let mut keys = Vec::new();
+  match row {
+    BL::KeyboardRow::Function => return function_row(),
+    BL::KeyboardRow::Number => {
+      keys.extend(chars(&[
+        ("²", "²", other(0xb2, 0xde)),
+        ("&", "1", K::Numrow1Key),
+        ("é", "2", K::Numrow2Key),
+        ("\"", "3", K::Numrow3Key),
+        ("'", "4", K::Numrow4Key),
+        ("(", "5", K::Numrow5Key),
+        ("-", "6", K::Numrow6Key),
+        ("è", "7", K::Numrow7Key),
+        ("_", "8", K::Numrow8Key),
+        ("ç", "9", K::Numrow9Key),
+        ("à", "0", K::Numrow0Key),
+        (")", "°", other(0x29, 0xdb)),
+        ("=", "+", other(0x3d, 0xbb)),
+      ]));
+      keys.push(wide("bksp", K::BackspaceKey, 2.0));
+    }
+    BL::KeyboardRow::Top => {
+      keys.push(wide("tab", K::TabKey, 1.5));
+      keys.extend(chars(&[
+        ("a", "A", K::AKey),
+        ("z", "Z", K::ZKey),
+        ("e", "E", K::EKey),
+        ("r", "R", K::RKey),
+        ("t", "T", K::TKey),
+        ("y", "Y", K::YKey),
+        ("u", "U", K::UKey),
+        ("i", "I", K::IKey),
+        ("o", "O", K::OKey),
+        ("p", "P", K::PKey),
+        ("^", "¨", other(0xfe52, 0xdd)),
+        ("$", "£", other(0x24, 0xba)),
+      ]));
+      keys.push(KeyCap {
+        width: 1.5,
+        ..cap("*", "µ", other(0x2a, 0xdc))
+      });
+    }
+    BL::KeyboardRow::Home => {
+      keys.push(wide("caps", K::CapsLockKey, 1.75));
+      keys.extend(chars(&[
+        ("q", "Q", K::QKey),
+        ("s", "S", K::SKey),
+        ("d", "D", K::DKey),
+        ("f", "F", K::FKey),
+        ("g", "G", K::GKey),
+        ("h", "H", K::HKey),
+        ("j", "J", K::JKey),
+        ("k", "K", K::KKey),
+        ("l", "L", K::LKey),
+        ("m", "M", K::MKey),
+        ("ù", "%", other(0xf9, 0xc0)),
+      ]));
+      keys.push(wide("enter", K::EnterKey, 2.25));
+    }
+    BL::KeyboardRow::Bottom => {
+      keys.push(shift(2.25));
+      keys.extend(chars(&[
+        ("w", "W", K::WKey),
+        ("x", "X", K::XKey),
+        ("c", "C", K::CKey),
+        ("v", "V", K::VKey),
+        ("b", "B", K::BKey),
+        ("n", "N", K::NKey),
+        (",", "?", other(0x2c, 0xbc)),
+        (";", ".", other(0x3b, 0xbe)),
+        (":", "/", other(0x3a, 0xbf)),
+        ("!", "§", other(0x21, 0xdf)),
+      ]));
+      keys.push(shift(1.75));
+    }
+    BL::KeyboardRow::Space => return space_row(),
+  }
+  keys
+}
+
+fn dvorak_row(row: &BL::KeyboardRow) -> Vec<KeyCap> {
+  let mut keys = Vec::new();
+  match row {
+    BL::KeyboardRow::Function => return function_row(),
+    BL::KeyboardRow::Number => {
+      keys.extend(chars(&[
+        ("`", "~", other(0x60, 0xc0)),
+        ("1", "!", K::Numrow1Key),
+        ("2", "@", K::Numrow2Key),
+        ("3", "#", K::Numrow3Key),
+        ("4", "$", K::Numrow4Key),
+        ("5", "%", K::Numrow5Key),
+        ("6", "^", K::Numrow6Key),
+        ("7", "&", K::Numrow7Key),
+        ("8", "*", K::Numrow8Key),
+        ("9", "(", K::Numrow9Key),
+        ("0", ")", K::Numrow0Key),
+        ("[", "{", other(0x5b, 0xdb)),
+        ("]", "}", other(0x5d, 0xdd)),
+      ]));
+      keys.push(wide("bksp", K::BackspaceKey, 2.0));
+    }
+    BL::KeyboardRow::Top => {
+      keys.push(wide("tab", K::TabKey, 1.5));
+      keys.extend(chars(&[
+        ("'", "\"", other(0x27, 0xde)),
+        (",", "<", other(0x2c, 0xbc)),
+        (".", ">", other(0x2e, 0xbe)),
+        ("p", "P", K::PKey),
+        ("y", "Y", K::YKey),
+        ("f", "F", K::FKey),
+        ("g", "G", K::GKey),
+        ("c", "C", K::CKey),
+        ("r", "R", K::RKey),
+        ("l", "L", K::LKey),
+        ("/", "?", other(0x2f, 0xbf)),
+        ("=", "+", other(0x3d, 0xbb)),
+      ]));
+      keys.push(KeyCap {
+        width: 1.5,
+        ..cap("\\", "|", other(0x5c, 0xdc))
+      });
+    }
+    BL::KeyboardRow::Home => {
+      keys.push(wide("caps", K::CapsLockKey, 1.75));
+      keys.extend(chars(&[
+        ("a", "A", K::AKey),
+        ("o", "O", K::OKey),
+        ("e", "E", K::EKey),
+        ("u", "U", K::UKey),
+        ("i", "I", K::IKey),
+        ("d", "D", K::DKey),
+        ("h", "H", K::HKey),
+        ("t", "T", K::TKey),
+        ("n", "N", K::NKey),
+        ("s", "S", K::SKey),
+        ("-", "_", other(0x2d, 0xbd)),
+      ]));
+      keys.push(wide("enter", K::EnterKey, 2.25));
+    }
+    BL::KeyboardRow::Bottom => {
+      keys.push(shift(2.25));
+      keys.extend(chars(&[
+        (";", ":", other(0x3b, 0xba)),
+        ("q", "Q", K::QKey),
+        ("j", "J", K::JKey),
+        ("k", "K", K::KKey),
+        ("x", "X", K::XKey),
+        ("b", "B", K::BKey),
+        ("m", "M", K::MKey),
+        ("w", "W", K::WKey),
+        ("v", "V", K::VKey),
+        ("z", "Z", K::ZKey),
+      ]));
+      keys.push(shift(2.75));
+    }
+    BL::KeyboardRow::Space => return space_row(),
+  }
+  keys
+}
+
+fn numpad_rows() -> Vec<Vec<KeyCap>> {
+  vec![
+    vec![
+      wide("num", K::NumLockKey, 1.0),
+      wide("/", other(0xffaf, 0x6f), 1.0),
+      wide("*", other(0xffaa, 0x6a), 1.0),
+      wide("-", other(0xffad, 0x6d), 1.0),
+    ],
+    vec![
+      wide("7", K::Numpad7Key, 1.0),
+      wide("8", K::Numpad8Key, 1.0),
+      wide("9", K::Numpad9Key, 1.0),
+      wide("+", other(0xffab, 0x6b), 1.0),
+    ],
+    vec![
+      wide("4", K::Numpad4Key, 1.0),
+      wide("5", K::Numpad5Key, 1.0),
+      wide("6", K::Numpad6Key, 1.0),
+      wide("bksp", K::BackspaceKey, 1.0),
+    ],
+    vec![
+      wide("1", K::Numpad1Key, 1.0),
+      wide("2", K::Numpad2Key, 1.0),
+      wide("3", K::Numpad3Key, 1.0),
+      wide("enter", K::EnterKey, 1.0),
+    ],
+    vec![
+      wide("0", K::Numpad0Key, 2.0),
+      wide(".", other(0xffae, 0x6e), 1.0),
+      wide("tab", K::TabKey, 1.0),
+    ],
+  ]
+}
+
+const DEFAULT_ROWS: [BL::KeyboardRow; 5] = [
+  BL::KeyboardRow::Number,
+  BL::KeyboardRow::Top,
+  BL::KeyboardRow::Home,
+  BL::KeyboardRow::Bottom,
+  BL::KeyboardRow::Space,
+];
+
+fn layout_rows(
+  layout: &BL::KeyboardLayout,
+  rows: &Option<Vec<BL::KeyboardRow>>,
+) -> Vec<Vec<KeyCap>> {
+  let rowfn = match layout {
+    BL::KeyboardLayout::Numpad => return numpad_rows(),
+    BL::KeyboardLayout::FunctionRow => return vec![function_row()],
+    BL::KeyboardLayout::Qwerty => qwerty_row,
+    BL::KeyboardLayout::Azerty => azerty_row,
+    BL::KeyboardLayout::Dvorak => dvorak_row,
+  };
+  match rows {
+    Some(rs) => rs.iter().map(rowfn).collect(),
+    None => DEFAULT_ROWS.iter().map(rowfn).collect(),
+  }
+}
+
+// add the keyboard as a vertical sizer of rows.
+pub fn add_keyboard<'a>(
+  gui: &'a mut G::Gui,
+  layout: &BL::KeyboardLayout,
+  rows: &Option<Vec<BL::KeyboardRow>>,
+) -> Result<&'a mut G::Gui, FError> {
+  let rows = layout_rows(layout, rows);
+  let rowprops = vec![1.0 / rows.len().max(1) as f32; rows.len()];
+  let mut g = gui.add_sizer(C::Orientation::Vertical, Some(rowprops))?;
+  for row in rows {
+    let total: f32 = row.iter().map(|c| c.width).sum();
+    let props = row.iter().map(|c| c.width / total).collect();
+    g = g.add_sizer(C::Orientation::Horizontal, Some(props))?;
+    for c in row {
+      let name = if c.is_shift {
+        BL::ButtonName::KeyboardShift
+      } else {
+        BL::ButtonName::KeyboardKey {
+          keys: c.keys,
+          label: c.label.clone(),
+          shift_label: c.shift_label,
+        }
+      };
+      g = g.add_button(serde_lexpr::to_string(&name)?, Some(c.label))?;
+    }
+    g = g.end_sizer()?;
+  }
+  g.end_sizer()
+}
+
+// ---------------------------------------------------------------
+// shift state, shared by all the Keyboard controls on the page.
+// ---------------------------------------------------------------
+
+pub struct KeyboardState {
+  held: held::SharedHeld,
+  shifted: bool,
+}
+
+impl KeyboardState {
+  pub fn new(held: held::SharedHeld) -> KeyboardState {
+    KeyboardState {
+      held,
+      shifted: false,
+    }
+  }
+
+  pub fn on_button(&mut self, bn: &BL::ButtonName, pressed: bool, cn: &ControlNexus) {
+    match bn {
+      BL::ButtonName::KeyboardShift => {
+        if pressed {
+          let shifted = !self.shifted;
+          self.set_shift(shifted, cn);
+        } else {
+          // the page shows the button unpressed on release; put it back.
+          show_shift(cn, self.shifted);
+        }
+      }
+      BL::ButtonName::KeyboardKey {
+        keys,
+        label: _,
+        shift_label: _,
+      } => {
+        let mut h = held::lock(&self.held);
+        if pressed {
+          for k in keys {
+            h.press_key(k);
+          }
+        } else {
+          for k in keys.iter().rev() {
+            h.release_key(k);
+          }
+          drop(h);
+          // shift lasts for one key.
+          if self.shifted {
+            self.set_shift(false, cn);
+          }
+        }
+      }
+      _ => (),
+    }
+  }
+
+  fn set_shift(&mut self, shifted: bool, cn: &ControlNexus) {
+    self.shifted = shifted;
+    {
+      let mut h = held::lock(&self.held);
+      if shifted {
+        h.press_key(&K::LShiftKey);
+      } else {
+        h.release_key(&K::LShiftKey);
+      }
+    }
+    show_shift(cn, shifted);
+    for (id, label, shift_label) in keyboard_keys(cn) {
+      cn.update(&cu::UpdateMsg::Button {
+        control_id: id,
+        state: None,
+        label: Some(if shifted { shift_label } else { label }),
+      });
+    }
+  }
+}
+
+fn show_shift(cn: &ControlNexus, shifted: bool) {
+  let state = if shifted {
+    cu::PressState::Pressed
+  } else {
+    cu::PressState::Unpressed
+  };
+  for id in find_buttons(cn, |bn| matches!(bn, BL::ButtonName::KeyboardShift)) {
+    cn.update(&cu::UpdateMsg::Button {
+      control_id: id,
+      state: Some(state.clone()),
+      label: None,
+    });
+  }
+}
+
+// ids and labels of all the keyboard keys.
+fn keyboard_keys(cn: &ControlNexus) -> Vec<(Vec<i32>, String, String)> {
+  let ci = match cn.ci.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  let mut keys = Vec::new();
+  for (id, c) in ci.cm.iter() {
+    match serde_lexpr::from_str::<BL::ButtonName>(c.name()) {
+      Ok(BL::ButtonName::KeyboardKey {
+        keys: _,
+        label,
+        shift_label,
+      }) if label != shift_label => keys.push((id.clone(), label, shift_label)),
+      _ => (),
+    }
+  }
+  keys
+}
+
+fn find_buttons<F>(cn: &ControlNexus, pred: F) -> Vec<Vec<i32>>
+where
+  F: Fn(&BL::ButtonName) -> bool,
+{
+  let ci = match cn.ci.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  ci.cm
+    .iter()
+    .filter(
+      |(_, c)| match serde_lexpr::from_str::<BL::ButtonName>(c.name()) {
+        Ok(bn) => pred(&bn),
+        Err(_) => false,
+      },
+    )
+    .map(|(id, _)| id.clone())
+    .collect()
+}
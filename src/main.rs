@@ -8,11 +8,11 @@ use std::fs::File;
 use std::io::Read;
 use std::io::Write;
 use std::path::Path;
-use std::time::SystemTime;
-use touchpage::control_nexus::{ControlNexus, ControlUpdateProcessor};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use touchpage::control_nexus::{ControlNexus, ControlUpdateProcessor, PrintUpdateMsg};
 use touchpage::control_updates as cu;
-use touchpage::json as J;
-use touchpage::webserver;
 use touchpage::websocketserver;
 // use serde_lexpr::{to_string_pretty, from_str}
 
@@ -21,7 +21,33 @@ use inputbot::{MouseButton, MouseCursor};
 #[cfg(target_os = "windows")]
 use inputbot::{MouseButton, MouseCursor, MouseWheel};
 
+mod access;
+mod actions;
+mod api;
 mod buildlisp;
+mod commands;
+mod expand;
+mod follow;
+mod formats;
+mod grid;
+mod held;
+mod http;
+mod input;
+mod jog;
+mod joystick;
+mod keyboard;
+mod mdns;
+mod midi;
+mod mpris;
+mod osc;
+mod pagesocket;
+mod pretty;
+mod profiles;
+mod qr;
+mod server;
+mod slider;
+mod sources;
+mod window;
 
 use buildlisp::{
   Control::{Key, Label, MouseXy, ScrollButton, Sizer},
@@ -34,17 +60,13 @@ extern crate serde;
 extern crate serde_json;
 extern crate serde_lexpr;
 
+// what touchpage's websocket server is given for a port, so it can't
+// listen; see main().
+const NO_PORT: &str = "none";
+
 fn default_prefs() -> BL::Settings {
   BL::Settings {
-    prefs: BL::Prefs {
-      xmult: 1000.0,
-      ymult: 1000.0,
-      max_tap_duration: 100,
-      show_press_duration: false,
-      scroll_threshold: 10,
-      html_port: 8000,
-      websocket_port: 9000,
-    },
+    prefs: BL::Prefs::default(),
     gui: BL::Gui {
       title: "example".to_string(),
       control: Sizer {
@@ -54,11 +76,17 @@ fn default_prefs() -> BL::Settings {
             orientation: BL::Orientation::Horizontal,
             controls: vec![
               Label {
+                name: None,
                 label: "label1".to_string(),
+                source: None,
+                interval: None,
                 proportion: None,
               },
               Label {
+                name: None,
                 label: "label2".to_string(),
+                source: None,
+                interval: None,
                 proportion: None,
               },
             ],
@@ -110,24 +138,30 @@ fn default_prefs() -> BL::Settings {
 fn main() {
   // read in the settings json.
   let args = env::args();
-  let mut iter = args.skip(1); // skip the program name
+  let mut iter = args.skip(1).peekable(); // skip the program name
   let mut prefs_filename = None;
-  match iter.next() {
-    Some(s1) => match s1.as_str() {
+  let mut qr_png_filename = None;
+  while let Some(s1) = iter.next() {
+    match s1.as_str() {
       "--help" => {
         println!("usage:");
         println!("mousepage");
         println!("mousepage --help");
         println!("mousepage <prefs filename>");
-        println!("mousepage --writeprefs <filename>");
+        println!("mousepage --qr-png <png filename> <prefs filename>");
+        println!("mousepage --writeprefs <filename> [--comments]");
+        println!("mousepage --convert <in filename> <out filename> [--comments]");
+        println!("prefs files can be .scm, .json, .toml or .yaml");
         return;
       }
       "--writeprefs" => match iter.next() {
         Some(filename) => {
           let p = default_prefs();
-          match serde_lexpr::to_string(&p) {
+          let format = formats::from_extension(filename.as_str()).unwrap_or(formats::Format::Sexp);
+          let comments = iter.peek().map(|s| s.as_str()) == Some("--comments");
+          match formats::write_settings(&p, format, comments) {
             Err(e) => {
-              println!("error converting prefs to s-expression: {:?}", e);
+              println!("error converting prefs to {:?}: {}", format, e);
               return;
             }
             Ok(s) => {
@@ -144,16 +178,39 @@ fn main() {
           return;
         }
       },
+      "--convert" => match (iter.next(), iter.next()) {
+        (Some(infile), Some(outfile)) => {
+          let comments = iter.peek().map(|s| s.as_str()) == Some("--comments");
+          match convert(infile.as_str(), outfile.as_str(), comments) {
+            Err(e) => println!("error converting prefs: {}", e),
+            _ => println!("converted {} to {}", infile, outfile),
+          }
+          return;
+        }
+        _ => {
+          println!("--convert needs an input and an output filename");
+          return;
+        }
+      },
+      "--qr-png" => match iter.next() {
+        Some(filename) => {
+          qr_png_filename = Some(filename);
+        }
+        None => {
+          println!("no filename supplied for --qr-png option");
+          return;
+        }
+      },
       pf => {
         prefs_filename = Some(pf.to_string());
       }
-    },
-    _ => (),
+    }
   }
 
-  let settings = match prefs_filename {
+  let prefs_file = prefs_filename.clone();
+  let mut settings = match prefs_filename {
     Some(pf) => match load_string(pf.as_str()) {
-      Ok(s) => match serde_lexpr::from_str(s.as_str()) {
+      Ok(s) => match formats::parse_settings(s.as_str(), pf.as_str()) {
         Ok(p) => p,
         Err(e) => {
           println!("error loading prefs: {}", e);
@@ -179,12 +236,11 @@ fn main() {
     serde_json::to_string_pretty(&p).unwrap_or("error serializing prefs".to_string())
   );
 
-  let rootv: Result<String, FError> = BL::build_gui(settings.gui, settings.colors.unwrap_or(vec![]))
-    .and_then(|gui| gui.to_root())
-    .map(|root| J::serialize_root(&root))
-    .and_then(|rootv| serde_json::to_string_pretty(&rootv).map_err(|_| err_msg("uh oh")));
+  let title = settings.gui.title.clone();
+  let uses_midi = BL::uses_midi(&settings.gui.control);
+  let sourced_labels = BL::sourced_labels(&mut settings.gui.control);
 
-  let guijson = match rootv {
+  let guijson = match BL::gui_json(settings.gui, settings.colors.unwrap_or(vec![])) {
     Ok(s) => s,
     Err(e) => {
       println!("error loading controls! {}", e);
@@ -192,145 +248,392 @@ fn main() {
     }
   };
 
-  let hp = p.html_port.to_string();
-  let wp = p.websocket_port.to_string();
+  let mdns_enabled = p.mdns.unwrap_or(true);
+  let mdns_interfaces = p.mdns_interfaces.clone();
+
+  // the virtual MIDI port, if anything sends MIDI.
+  let midi = midi::Midi::new(p.midi_port.clone());
+  if uses_midi {
+    midi.open();
+  }
+
+  // keys, buttons and notes held down on the host, so they can be
+  // released if a client goes away mid-press.
+  let held = held::new_shared(midi.clone());
+  let release_timeout = p.release_timeout.unwrap_or(0);
+  if release_timeout > 0 {
+    held::start_idle_watch(held.clone(), Duration::from_millis(release_timeout.into()));
+  }
+
+  let access = match (
+    access::parse_list(&p.trusted_proxies),
+    access::parse_list(&p.allowed_clients),
+  ) {
+    (Ok(trusted_proxies), Ok(allowed_clients)) => access::Access {
+      trusted_proxies,
+      allowed_clients,
+    },
+    (Err(e), _) | (_, Err(e)) => {
+      println!("error in prefs: {}", e);
+      return;
+    }
+  };
+
+  let mut server_config = server::ServerConfig {
+    ip: "0.0.0.0".to_string(),
+    html_port: p.html_port,
+    websocket_port: p.websocket_port,
+    single_port: p.single_port.unwrap_or(false),
+    base_path: server::normalize_base_path(p.base_path.as_ref()),
+    public_websocket_url: p.public_websocket_url.clone(),
+    access,
+    held: held.clone(),
+    clients: Arc::new(Mutex::new(Vec::new())),
+    token: p.token.clone(),
+    api: None,
+  };
+
+  let osc = match &p.osc {
+    Some(op) => match osc::Osc::new(op, guijson.as_str()) {
+      Ok(o) => Some(o),
+      Err(e) => {
+        println!("error setting up OSC: {}", e);
+        None
+      }
+    },
+    None => None,
+  };
+
+  let mpris = mpris::Mpris::new(p.mpris_player.clone());
+  let mpris_label = p.mpris_label.clone();
+
+  let control_socket = p.control_socket.clone();
+  let profile_files = p.profiles.clone().unwrap_or_default();
+  let profile_held = held.clone();
+  // the window with the focus, for labels and following it.
+  let window_provider = window::new_shared(&p.window_provider);
+  let follow_window = p.follow_window.clone();
+  // for the api; the token stays out of it.
+  let mut prefs_json = serde_json::to_value(&p).unwrap_or(serde_json::Value::Null);
+  if let Some(o) = prefs_json.as_object_mut() {
+    o.remove("token");
+  }
+
+  let gui_loads = Arc::new(AtomicUsize::new(0));
 
   // the 'ControlUpdateProcessor' does something when an update message comes in.
   let cup = MouseUpdate {
     last_loc: None,
     press_start: None,
-    scroll_mode: false,
+    held: held.clone(),
+    sliders: slider::SliderState::new(midi.clone()),
+    actions: actions::Actions::new(mpris.clone(), held.clone(), window_provider.clone()),
+    jogs: jog::JogState::new(),
+    joysticks: joystick::JoystickState::new(held.clone()),
+    keyboards: keyboard::KeyboardState::new(held),
+    midi: midi.clone(),
+    osc,
+    gui_loads: gui_loads.clone(),
+    seen_gui_loads: 0,
     prefs: p,
   };
 
-  // start the websocket server.  mandatory for receiving control messages.
+  // shared with the control api, which can press buttons too.
+  let processor: api::Processor = Arc::new(Mutex::new(cup));
+
+  // set up the controls.  touchpage's websocket server would listen on a
+  // port anyone on this computer could connect to, so the page's websocket
+  // goes through our web server and pagesocket instead.  start() is the
+  // only way to get a ControlNexus, since touchpage keeps its broadcaster
+  // type private, so it's given a port it can't listen on.  blocking, the
+  // failed bind comes back right away instead of leaving a thread behind.
   match websocketserver::start(
     guijson.as_str(),
-    Box::new(cup),
-    "0.0.0.0",
-    wp.as_str(),
-    false,
+    Box::new(PrintUpdateMsg {}),
+    "127.0.0.1",
+    NO_PORT,
+    true,
   ) {
-    Ok(_) => (),
+    Ok(cn) => {
+      println!("(that's expected; the page's websocket is served by mousepage)");
+      if let Some(label) = mpris_label {
+        mpris.start_label(label, cn.clone());
+      }
+      let sources = sources::Sources::new(cn.clone(), window_provider.clone());
+      sources.start(sourced_labels);
+      let api = api::Api {
+        cn: cn.clone(),
+        processor,
+        profiles: profiles::Profiles::new(
+          cn,
+          profile_held,
+          midi,
+          profile_files,
+          prefs_file,
+          sources,
+          gui_loads,
+        ),
+        clients: server_config.clients.clone(),
+        prefs: prefs_json,
+      };
+      server_config.api = Some(api.clone());
+      if let Some(rules) = follow_window {
+        match follow::start(&rules, window_provider, api.profiles.clone()) {
+          Ok(()) => (),
+          Err(e) => println!("error in follow_window: {}", e),
+        }
+      }
+      if let Some(path) = control_socket {
+        match api::start_socket(path.as_str(), api) {
+          Ok(()) => (),
+          Err(e) => println!("error starting control socket {}: {}", path, e),
+        }
+      }
+    }
     Err(e) => println!("error starting websocket server: {},", e),
   }
 
-  // start the webserver.  not necessary if you want to serve up the html with your
-  // own server.
-  webserver::start("0.0.0.0", hp.as_str(), wp.as_str(), None, true);
+  // show the page url and a QR code for it, so the phone can find the page.
+  qr::show_page_url(
+    server_config.html_port,
+    server_config.base_path.as_str(),
+    server_config.token.as_deref(),
+    qr_png_filename.as_deref(),
+  );
+
+  // advertise the page on the LAN.  keep the daemon around, dropping it
+  // ends the advertisement.
+  let _mdns_daemon = if mdns_enabled {
+    match mdns::advertise(
+      title.as_str(),
+      server_config.html_port,
+      server_config.base_path.as_str(),
+      server_config.public_websocket_port(),
+      mdns_interfaces.as_ref(),
+    ) {
+      Ok(d) => Some(d),
+      Err(e) => {
+        println!("error starting mDNS advertisement: {}", e);
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  // start the webserver, which serves the page and passes websocket
+  // connections through to the websocket server.
+  match server::start(server_config) {
+    Ok(_) => (),
+    Err(e) => println!("error starting web server: {}", e),
+  }
 }
 
 pub struct MouseUpdate {
   last_loc: Option<(f32, f32)>,
   press_start: Option<SystemTime>,
-  scroll_mode: bool,
+  held: held::SharedHeld,
+  sliders: slider::SliderState,
+  actions: actions::Actions,
+  jogs: jog::JogState,
+  joysticks: joystick::JoystickState,
+  keyboards: keyboard::KeyboardState,
+  midi: midi::Midi,
+  osc: Option<osc::Osc>,
+  // bumped by profiles on each gui load.
+  gui_loads: Arc<AtomicUsize>,
+  seen_gui_loads: usize,
   prefs: Prefs,
 }
 
-impl ControlUpdateProcessor for MouseUpdate {
-  fn on_update_received(&mut self, update: &cu::UpdateMsg, cn: &mut ControlNexus) -> () {
-    // println!("control update: {:?}", update);
-    match update {
-      cu::UpdateMsg::XY {
-        control_id: _,
-        state,
-        location,
-        label: _,
-      } => {
-        match location {
-          Some((x, y)) => match self.last_loc {
-            Some((lx, ly)) => {
-              let nx = (self.prefs.xmult * (x - lx)).round() as i32;
-              let ny = (self.prefs.ymult * (y - ly)).round() as i32;
-              if self.scroll_mode {
-                #[cfg(target_os = "linux")]
-                {
-                  let mut nlx = lx;
-                  let mut nly = ly;
-                  if i32::abs(nx) > self.prefs.scroll_threshold {
-                    if nx < 0 {
-                      MouseButton::OtherButton(6).press();
-                      MouseButton::OtherButton(6).release();
-                    } else {
-                      MouseButton::OtherButton(7).press();
-                      MouseButton::OtherButton(7).release();
-                    }
-                    nlx = *x;
-                  }
-
-                  if i32::abs(ny) > self.prefs.scroll_threshold {
-                    if ny < 0 {
-                      MouseButton::OtherButton(4).press();
-                      MouseButton::OtherButton(4).release();
-                    } else {
-                      MouseButton::OtherButton(5).press();
-                      MouseButton::OtherButton(5).release();
-                    }
+impl MouseUpdate {
+  // a profile switch or reload replaced the gui, so control ids mean
+  // other controls now.
+  fn gui_loaded(&mut self, cn: &ControlNexus) {
+    self.last_loc = None;
+    self.press_start = None;
+    self.sliders = slider::SliderState::new(self.midi.clone());
+    self.jogs = jog::JogState::new();
+    self.joysticks.forget_keys();
+    self.keyboards = keyboard::KeyboardState::new(self.held.clone());
+    if let Some(o) = &mut self.osc {
+      let ci = match cn.ci.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+      };
+      o.load_gui(ci.guijson.as_str());
+    }
+  }
 
-                    nly = *y;
-                  }
-                  self.last_loc = Some((nlx, nly));
+  // MouseXy; move the mouse like a touchpad, or scroll in scroll mode.
+  fn mouse_xy(&mut self, state: &Option<cu::PressState>, location: &Option<(f32, f32)>) {
+    match location {
+      Some((x, y)) => match self.last_loc {
+        Some((lx, ly)) => {
+          let nx = (self.prefs.xmult * (x - lx)).round() as i32;
+          let ny = (self.prefs.ymult * (y - ly)).round() as i32;
+          let scroll_mode = held::lock(&self.held).scroll_mode;
+          if scroll_mode {
+            #[cfg(target_os = "linux")]
+            {
+              let mut nlx = lx;
+              let mut nly = ly;
+              if i32::abs(nx) > self.prefs.scroll_threshold {
+                if nx < 0 {
+                  MouseButton::OtherButton(6).press();
+                  MouseButton::OtherButton(6).release();
+                } else {
+                  MouseButton::OtherButton(7).press();
+                  MouseButton::OtherButton(7).release();
                 }
+                nlx = *x;
+              }
 
-                #[cfg(target_os = "windows")]
-                {
-                  MouseWheel.scroll_hor(nx);
-                  MouseWheel.scroll_ver(ny);
-                  self.last_loc = Some((*x, *y));
+              if i32::abs(ny) > self.prefs.scroll_threshold {
+                if ny < 0 {
+                  MouseButton::OtherButton(4).press();
+                  MouseButton::OtherButton(4).release();
+                } else {
+                  MouseButton::OtherButton(5).press();
+                  MouseButton::OtherButton(5).release();
                 }
-              } else {
-                MouseCursor.move_rel(nx, ny);
-                self.last_loc = Some((*x, *y));
-              };
+
+                nly = *y;
+              }
+              self.last_loc = Some((nlx, nly));
             }
-            None => {
+
+            #[cfg(target_os = "windows")]
+            {
+              MouseWheel.scroll_hor(nx);
+              MouseWheel.scroll_ver(ny);
               self.last_loc = Some((*x, *y));
             }
-          },
-          None => (),
-        };
-        match state {
-          Some(cu::PressState::Pressed) => match self.press_start {
-            None => {
-              self.press_start = Some(SystemTime::now());
-            }
-            _ => (),
-          },
+          } else {
+            MouseCursor.move_rel(nx, ny);
+            self.last_loc = Some((*x, *y));
+          };
+        }
+        None => {
+          self.last_loc = Some((*x, *y));
+        }
+      },
+      None => (),
+    };
+    match state {
+      Some(cu::PressState::Pressed) => match self.press_start {
+        None => {
+          self.press_start = Some(SystemTime::now());
+        }
+        _ => (),
+      },
 
-          None => match self.press_start {
-            None => {
-              self.press_start = Some(SystemTime::now());
-            }
-            _ => (),
-          },
+      None => match self.press_start {
+        None => {
+          self.press_start = Some(SystemTime::now());
+        }
+        _ => (),
+      },
 
-          Some(cu::PressState::Unpressed) => {
-            // reset last location, we'll start that again next press.
-            self.last_loc = None;
-
-            // check the press duration.  if its short enough we'll do a
-            // button press.
-            match self.press_start {
-              Some(lu) => {
-                let now = SystemTime::now();
-                match now.duration_since(lu) {
-                  Ok(duration) => {
-                    if self.prefs.show_press_duration {
-                      println!("press duration: {}", duration.as_millis());
-                    }
-                    if duration.as_millis() < self.prefs.max_tap_duration.into() {
-                      MouseButton::LeftButton.press();
-                      MouseButton::LeftButton.release();
-                    }
-                  }
-                  Err(_) => (),
+      Some(cu::PressState::Unpressed) => {
+        // reset last location, we'll start that again next press.
+        self.last_loc = None;
+
+        // check the press duration.  if its short enough we'll do a
+        // button press.
+        match self.press_start {
+          Some(lu) => {
+            let now = SystemTime::now();
+            match now.duration_since(lu) {
+              Ok(duration) => {
+                if self.prefs.show_press_duration {
+                  println!("press duration: {}", duration.as_millis());
+                }
+                if duration.as_millis() < self.prefs.max_tap_duration.into() {
+                  MouseButton::LeftButton.press();
+                  MouseButton::LeftButton.release();
                 }
               }
-              _ => (),
+              Err(_) => (),
             }
-            self.press_start = None;
           }
-        };
+          _ => (),
+        }
+        self.press_start = None;
+      }
+    };
+  }
+}
+
+impl ControlUpdateProcessor for MouseUpdate {
+  fn on_update_received(&mut self, update: &cu::UpdateMsg, cn: &mut ControlNexus) -> () {
+    // println!("control update: {:?}", update);
+    held::lock(&self.held).touch();
+    let loads = self.gui_loads.load(Ordering::SeqCst);
+    if loads != self.seen_gui_loads {
+      self.seen_gui_loads = loads;
+      self.gui_loaded(cn);
+    }
+    if let Some(o) = &self.osc {
+      o.on_update(update);
+      if o.only() {
+        return;
+      }
+    }
+    match update {
+      cu::UpdateMsg::XY {
+        control_id,
+        state,
+        location,
+        label: _,
+      } => {
+        let xyname = cn
+          .get_name(control_id)
+          .and_then(|name| serde_lexpr::from_str::<BL::XyName>(name.as_str()).ok());
+        match xyname {
+          Some(BL::XyName::Jog {
+            clockwise,
+            counter_clockwise,
+            steps_per_revolution,
+          }) => self.jogs.on_update(
+            &clockwise,
+            &counter_clockwise,
+            steps_per_revolution,
+            control_id,
+            state,
+            location,
+          ),
+          Some(BL::XyName::Joystick { action, deadzone }) => self
+            .joysticks
+            .on_update(&action, deadzone, control_id, state, location),
+          // y is 0 at the top, but the top should be max.
+          Some(BL::XyName::Midi {
+            channel,
+            x_cc,
+            y_cc,
+            min,
+            max,
+          }) => {
+            if let Some((x, y)) = location {
+              self.midi.cc(channel, x_cc, *x as f64, min, max);
+              self.midi.cc(channel, y_cc, 1.0 - *y as f64, min, max);
+            }
+          }
+          None => self.mouse_xy(state, location),
+        }
+      }
+      cu::UpdateMsg::Slider {
+        control_id,
+        state,
+        location,
+        label: _,
+      } => {
+        if let Some(name) = cn.get_name(control_id) {
+          match serde_lexpr::from_str::<BL::SliderAction>(name.as_str()) {
+            Ok(action) => self.sliders.on_update(&action, control_id, state, location),
+            Err(e) => println!("slider action error: {:?}", e),
+          }
+        }
       }
       cu::UpdateMsg::Button {
         control_id, state, ..
@@ -346,20 +649,32 @@ impl ControlUpdateProcessor for MouseUpdate {
           match mb {
             Ok(blmb) => {
               if pr {
-                BL::convert_mousebutton(&blmb).press()
+                held::lock(&self.held).press_button(&blmb)
               } else {
-                BL::convert_mousebutton(&blmb).release()
+                held::lock(&self.held).release_button(&blmb)
               }
             }
             Err(_) => {
               if name == "S" {
                 // scroll.
                 if pr {
-                  self.scroll_mode = true;
+                  held::lock(&self.held).set_scroll_mode(true);
                   self.press_start = None;
                 } else {
-                  self.scroll_mode = false;
+                  held::lock(&self.held).set_scroll_mode(false);
                 };
+              } else if name == BL::RELEASE_ALL {
+                if pr {
+                  println!("releasing all held keys, buttons and notes");
+                  held::lock(&self.held).release_all();
+                }
+              } else if let Ok(bn) = serde_lexpr::from_str::<BL::ButtonName>(name.as_str()) {
+                match bn {
+                  BL::ButtonName::KeyboardKey { .. } | BL::ButtonName::KeyboardShift => {
+                    self.keyboards.on_button(&bn, pr, cn)
+                  }
+                  _ => self.actions.on_button(&bn, name.as_str(), pr, cn),
+                }
               } else {
                 // assume the name is a KeybdKey vec.
                 let keys: Vec<BL::KeybdKey> = match serde_lexpr::from_str(name.as_str()) {
@@ -370,13 +685,14 @@ impl ControlUpdateProcessor for MouseUpdate {
                   }
                 };
 
+                let mut h = held::lock(&self.held);
                 if pr {
                   for k in &keys {
-                    BL::convert_keybdkey(&k).press();
+                    h.press_key(&k);
                   }
                 } else {
                   for k in keys.iter().rev() {
-                    BL::convert_keybdkey(&k).release();
+                    h.release_key(&k);
                   }
                 }
               }
@@ -400,6 +716,24 @@ const ERRORUI: &'static str = r##"
     }
 }"##;
 
+// read prefs in one format and write them in another, going by the
+// file extensions.
+fn convert(infile: &str, outfile: &str, comments: bool) -> Result<(), FError> {
+  let format = match formats::from_extension(outfile) {
+    Some(f) => f,
+    None => {
+      return Err(err_msg(format!(
+        "can't tell what format {} should be from its extension",
+        outfile
+      )))
+    }
+  };
+  let s = load_string(infile).map_err(|e| err_msg(format!("{}: {}", infile, e)))?;
+  let settings = formats::parse_settings(s.as_str(), infile)?;
+  let out = formats::write_settings(&settings, format, comments)?;
+  write_string(out.as_str(), outfile).map_err(|e| err_msg(format!("{}: {}", outfile, e)))
+}
+
 fn load_string(file_name: &str) -> Result<String, Box<dyn std::error::Error>> {
   let path = &Path::new(&file_name);
   let mut inf = File::open(path)?;
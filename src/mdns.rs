@@ -0,0 +1,69 @@
+// ---------------------------------------------------------------
+// advertise the control page over mDNS / DNS-SD, so phones and
+// companion apps can find it without knowing the host's ip.
+// ---------------------------------------------------------------
+use failure::Error as FError;
+use mdns_sd::{IfKind, ServiceDaemon, ServiceInfo};
+
+const SERVICE_TYPE: &str = "_http._tcp.local.";
+
+// name to advertise under; 'mousepage-<hostname>'.
+pub fn instance_name() -> String {
+  let host = match hostname::get() {
+    Ok(h) => h.to_string_lossy().to_string(),
+    Err(_) => "unknown".to_string(),
+  };
+  // just the first label, in case the hostname is fully qualified.
+  let short = host.split('.').next().unwrap_or("unknown");
+  format!("mousepage-{}", short)
+}
+
+// start an mDNS responder for the page.  the service stays registered
+// as long as the returned daemon is alive.
+// if interfaces are given, only those are used; "lo" may be listed to
+// advertise on loopback, which is off by default.  an empty list is the
+// same as none.
+pub fn advertise(
+  title: &str,
+  html_port: i32,
+  base_path: &str,
+  websocket_port: i32,
+  interfaces: Option<&Vec<String>>,
+) -> Result<ServiceDaemon, FError> {
+  let daemon = ServiceDaemon::new()?;
+
+  match interfaces {
+    Some(ifs) if !ifs.is_empty() => {
+      daemon.disable_interface(IfKind::All)?;
+      for i in ifs {
+        daemon.enable_interface(i)?;
+      }
+    }
+    _ => (),
+  }
+
+  let name = instance_name();
+  let host = format!("{}.local.", name);
+  let port = html_port.to_string();
+  let wsport = websocket_port.to_string();
+  let properties = [
+    ("title", title),
+    ("path", base_path),
+    ("port", port.as_str()),
+    ("websocket_port", wsport.as_str()),
+  ];
+
+  let info = ServiceInfo::new(
+    SERVICE_TYPE,
+    name.as_str(),
+    host.as_str(),
+    "",
+    html_port as u16,
+    &properties[..],
+  )?
+  .enable_addr_auto();
+
+  daemon.register(info)?;
+  println!("advertising {}.{} on mDNS", name, SERVICE_TYPE);
+  Ok(daemon)
+}
@@ -0,0 +1,163 @@
+// ---------------------------------------------------------------
+// MIDI output through a virtual ALSA sequencer port, which synths and
+// DAWs can connect to like any other MIDI device.  buttons send notes
+// or control changes, sliders and MidiXy pads send control changes.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(target_os = "linux")]
+use midir::os::unix::VirtualOutput;
+#[cfg(target_os = "linux")]
+use midir::{MidiOutput, MidiOutputConnection};
+
+const DEFAULT_PORT: &str = "mousepage";
+
+struct State {
+  #[cfg(target_os = "linux")]
+  conn: Option<MidiOutputConnection>,
+  port: String,
+  // last control change value sent, by (channel, cc), so sliders and
+  // pads only send when the value changes.
+  last: HashMap<(u8, u8), u8>,
+}
+
+#[derive(Clone)]
+pub struct Midi {
+  state: Arc<Mutex<State>>,
+}
+
+impl Midi {
+  // the port isn't made until open(), so guis without any MIDI controls
+  // don't get one.
+  pub fn new(port: Option<String>) -> Midi {
+    let port = match port {
+      Some(p) if !p.is_empty() => p,
+      _ => DEFAULT_PORT.to_string(),
+    };
+    Midi {
+      state: Arc::new(Mutex::new(State {
+        #[cfg(target_os = "linux")]
+        conn: None,
+        port,
+        last: HashMap::new(),
+      })),
+    }
+  }
+
+  // make the virtual port.  if that fails the MIDI controls just print
+  // errors.
+  pub fn open(&self) {
+    let mut state = self.lock();
+    let port = state.port.clone();
+    match state.open(port.as_str()) {
+      Ok(()) => println!("MIDI port: {}", port),
+      Err(e) => println!("error making MIDI port {}: {}", port, e),
+    }
+  }
+
+  pub fn is_open(&self) -> bool {
+    self.lock().is_open()
+  }
+
+  fn lock(&self) -> MutexGuard<'_, State> {
+    match self.state.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  // a Button's Midi action; note on or cc 'on' when pressed, note off or
+  // cc 'off' when released.
+  pub fn button(&self, msg: &BL::MidiMessage, pressed: bool) {
+    let bytes = match msg {
+      BL::MidiMessage::Note {
+        channel,
+        note,
+        velocity,
+      } => {
+        let status = if pressed { 0x90 } else { 0x80 };
+        let velocity = if pressed { velocity.unwrap_or(127) } else { 0 };
+        [status | channel_bits(*channel), data(*note), data(velocity)]
+      }
+      BL::MidiMessage::Cc {
+        channel,
+        cc,
+        on,
+        off,
+      } => {
+        let value = if pressed {
+          on.unwrap_or(127)
+        } else {
+          off.unwrap_or(0)
+        };
+        self.lock().last.insert((*channel, *cc), data(value));
+        [0xB0 | channel_bits(*channel), data(*cc), data(value)]
+      }
+    };
+    self.lock().send(&bytes);
+  }
+
+  // control change for a slider or pad position, 0.0 to 1.0 across min
+  // to max.
+  pub fn cc(&self, channel: u8, cc: u8, position: f64, min: Option<u8>, max: Option<u8>) {
+    let min = data(min.unwrap_or(0)) as f64;
+    let max = data(max.unwrap_or(127)) as f64;
+    let position = position.clamp(0.0, 1.0);
+    let value = data((min + position * (max - min)).round() as u8);
+    let mut state = self.lock();
+    if state.last.get(&(channel, cc)) != Some(&value) {
+      state.last.insert((channel, cc), value);
+      state.send(&[0xB0 | channel_bits(channel), data(cc), value]);
+    }
+  }
+}
+
+// channels are 1 to 16 in the prefs, 0 to 15 on the wire.
+fn channel_bits(channel: u8) -> u8 {
+  channel.clamp(1, 16) - 1
+}
+
+// data bytes are 7 bits.
+fn data(b: u8) -> u8 {
+  b.min(127)
+}
+
+#[cfg(target_os = "linux")]
+impl State {
+  fn open(&mut self, port: &str) -> Result<(), String> {
+    let out = MidiOutput::new("mousepage").map_err(|e| e.to_string())?;
+    self.conn = Some(out.create_virtual(port).map_err(|e| e.to_string())?);
+    Ok(())
+  }
+
+  fn is_open(&self) -> bool {
+    self.conn.is_some()
+  }
+
+  fn send(&mut self, bytes: &[u8]) {
+    match &mut self.conn {
+      Some(c) => match c.send(bytes) {
+        Ok(()) => (),
+        Err(e) => println!("error sending MIDI: {}", e),
+      },
+      None => println!("no MIDI port, not sending {:02x?}", bytes),
+    }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl State {
+  fn open(&mut self, _port: &str) -> Result<(), String> {
+    Err("virtual MIDI ports are only on linux".to_string())
+  }
+
+  fn is_open(&self) -> bool {
+    false
+  }
+
+  fn send(&mut self, bytes: &[u8]) {
+    println!("no MIDI port, not sending {:02x?}", bytes);
+  }
+}
@@ -0,0 +1,240 @@
+// ---------------------------------------------------------------
+// media player control over MPRIS, on the D-Bus session bus, so
+// play/pause and friends work whatever window has the focus.  also
+// keeps a Label up to date with the current track.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use touchpage::control_nexus::ControlNexus;
+
+#[cfg(target_os = "linux")]
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+#[cfg(target_os = "linux")]
+use dbus::blocking::Connection;
+
+const BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const TIMEOUT: Duration = Duration::from_millis(500);
+const LABEL_INTERVAL: Duration = Duration::from_secs(1);
+
+struct State {
+  #[cfg(target_os = "linux")]
+  conn: Option<Connection>,
+  // player name from the prefs or SelectPlayer, like "vlc".
+  preferred: Option<String>,
+  // bus name of the player last used.
+  selected: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct Mpris {
+  state: Arc<Mutex<State>>,
+}
+
+impl Mpris {
+  pub fn new(preferred: Option<String>) -> Mpris {
+    Mpris {
+      state: Arc::new(Mutex::new(State {
+        #[cfg(target_os = "linux")]
+        conn: None,
+        preferred,
+        selected: None,
+      })),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, State> {
+    match self.state.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    }
+  }
+
+  pub fn run(&self, action: &BL::MprisAction) {
+    let mut state = self.lock();
+    match state.run(action) {
+      Ok(()) => (),
+      Err(e) => {
+        println!("mpris {:?}: {}", action, e);
+        // the bus may have gone away; connect again next time.
+        state.disconnect();
+      }
+    }
+  }
+
+  // "artist - title" of the player's current track.
+  pub fn track(&self) -> String {
+    let mut state = self.lock();
+    match state.track() {
+      Ok(t) => t,
+      Err(e) => {
+        state.disconnect();
+        format!("{}", e)
+      }
+    }
+  }
+
+  // show the current track in the named Label, checking every second.
+  pub fn start_label(&self, label: String, cn: ControlNexus) {
+    let mpris = self.clone();
+    thread::spawn(move || {
+      let mut shown = None;
+      loop {
+        let t = mpris.track();
+        if shown.as_ref() != Some(&t) {
+          cn.update_label(label.as_str(), t.as_str());
+          shown = Some(t);
+        }
+        thread::sleep(LABEL_INTERVAL);
+      }
+    });
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl State {
+  fn disconnect(&mut self) {
+    self.conn = None;
+  }
+
+  fn connection(&mut self) -> Result<&Connection, dbus::Error> {
+    if self.conn.is_none() {
+      self.conn = Some(Connection::new_session()?);
+    }
+    Ok(self.conn.as_ref().unwrap())
+  }
+
+  // bus names of the running players, sorted.
+  fn players(&mut self) -> Result<Vec<String>, dbus::Error> {
+    let conn = self.connection()?;
+    let bus = conn.with_proxy("org.freedesktop.DBus", "/org/freedesktop/DBus", TIMEOUT);
+    let (names,): (Vec<String>,) = bus.method_call("org.freedesktop.DBus", "ListNames", ())?;
+    let mut players: Vec<String> = names
+      .into_iter()
+      .filter(|n| n.starts_with(BUS_PREFIX))
+      .collect();
+    players.sort();
+    Ok(players)
+  }
+
+  // the selected player if it's still around, then the preferred one,
+  // then one that's playing, then any.
+  fn player(&mut self) -> Result<Option<String>, dbus::Error> {
+    let players = self.players()?;
+    match &self.selected {
+      Some(s) if players.contains(s) => return Ok(Some(s.clone())),
+      _ => (),
+    }
+    let preferred = match &self.preferred {
+      Some(p) => {
+        let name = format!("{}{}", BUS_PREFIX, p);
+        let instance = format!("{}.", name);
+        players
+          .iter()
+          .find(|b| **b == name || b.starts_with(instance.as_str()))
+          .cloned()
+      }
+      None => None,
+    };
+    let player = match preferred {
+      Some(p) => Some(p),
+      None => {
+        let conn = self.connection()?;
+        let playing = players.iter().find(|b| {
+          conn
+            .with_proxy(b.as_str(), PATH, TIMEOUT)
+            .get::<String>(PLAYER, "PlaybackStatus")
+            .map(|s| s == "Playing")
+            .unwrap_or(false)
+        });
+        playing.or(players.first()).cloned()
+      }
+    };
+    self.selected = player.clone();
+    Ok(player)
+  }
+
+  fn run(&mut self, action: &BL::MprisAction) -> Result<(), dbus::Error> {
+    match action {
+      BL::MprisAction::SelectPlayer(name) => {
+        self.preferred = Some(name.clone());
+        self.selected = None;
+        return Ok(());
+      }
+      BL::MprisAction::NextPlayer => {
+        let players = self.players()?;
+        let next = match self.player()? {
+          Some(cur) => match players.iter().position(|p| *p == cur) {
+            Some(i) => players.get((i + 1) % players.len()).cloned(),
+            None => players.first().cloned(),
+          },
+          None => None,
+        };
+        println!("mpris player: {}", next.as_deref().unwrap_or("none"));
+        self.selected = next;
+        return Ok(());
+      }
+      _ => (),
+    }
+
+    let player = match self.player()? {
+      Some(p) => p,
+      None => {
+        println!("mpris {:?}: no player running", action);
+        return Ok(());
+      }
+    };
+    let conn = self.connection()?;
+    let p = conn.with_proxy(player.as_str(), PATH, TIMEOUT);
+    match action {
+      BL::MprisAction::PlayPause => p.method_call(PLAYER, "PlayPause", ()),
+      BL::MprisAction::Play => p.method_call(PLAYER, "Play", ()),
+      BL::MprisAction::Pause => p.method_call(PLAYER, "Pause", ()),
+      BL::MprisAction::Stop => p.method_call(PLAYER, "Stop", ()),
+      BL::MprisAction::Next => p.method_call(PLAYER, "Next", ()),
+      BL::MprisAction::Previous => p.method_call(PLAYER, "Previous", ()),
+      // mpris wants microseconds.
+      BL::MprisAction::Seek(ms) => p.method_call(PLAYER, "Seek", (ms * 1000,)),
+      BL::MprisAction::Volume(change) => {
+        let v: f64 = p.get(PLAYER, "Volume")?;
+        p.set(PLAYER, "Volume", (v + change).clamp(0.0, 1.0))
+      }
+      BL::MprisAction::SelectPlayer(_) | BL::MprisAction::NextPlayer => Ok(()),
+    }
+  }
+
+  fn track(&mut self) -> Result<String, dbus::Error> {
+    let player = match self.player()? {
+      Some(p) => p,
+      None => return Ok("no player".to_string()),
+    };
+    let conn = self.connection()?;
+    let p = conn.with_proxy(player.as_str(), PATH, TIMEOUT);
+    let meta: dbus::arg::PropMap = p.get(PLAYER, "Metadata")?;
+    let title = dbus::arg::prop_cast::<String>(&meta, "xesam:title").cloned();
+    let artists = dbus::arg::prop_cast::<Vec<String>>(&meta, "xesam:artist")
+      .map(|a| a.join(", "))
+      .unwrap_or_default();
+    Ok(match title {
+      Some(t) if artists.is_empty() => t,
+      Some(t) => format!("{} - {}", artists, t),
+      None => player[BUS_PREFIX.len()..].to_string(),
+    })
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl State {
+  fn disconnect(&mut self) {}
+
+  fn run(&mut self, _action: &BL::MprisAction) -> Result<(), String> {
+    Err("MPRIS is only on linux".to_string())
+  }
+
+  fn track(&mut self) -> Result<String, String> {
+    Err("MPRIS is only on linux".to_string())
+  }
+}
@@ -0,0 +1,177 @@
+// ---------------------------------------------------------------
+// Open Sound Control output.  button presses, slider moves and xy pad
+// touches go out as OSC messages over UDP, for audio software and the
+// like.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use failure::err_msg;
+use failure::Error as FError;
+use std::collections::HashMap;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use tinyosc::{Argument, Message};
+use touchpage::control_updates as cu;
+use touchpage::controls;
+use touchpage::json as J;
+
+const DEFAULT_ADDRESS: &str = "/mousepage/{kind}/{label}";
+
+pub struct Osc {
+  socket: UdpSocket,
+  target: SocketAddr,
+  only: bool,
+  address: String,
+  scales: Vec<BL::OscScale>,
+  // control labels by id, as of when the gui was loaded.  toggles and
+  // keyboard keys change theirs, but their addresses shouldn't.
+  labels: HashMap<Vec<i32>, String>,
+}
+
+impl Osc {
+  pub fn new(prefs: &BL::OscPrefs, guijson: &str) -> Result<Osc, FError> {
+    let target = match (prefs.host.as_str(), prefs.port).to_socket_addrs()?.next() {
+      Some(a) => a,
+      None => return Err(err_msg(format!("OSC host {} not found", prefs.host))),
+    };
+    let socket = if target.is_ipv4() {
+      UdpSocket::bind("0.0.0.0:0")?
+    } else {
+      UdpSocket::bind("[::]:0")?
+    };
+
+    let labels = labels(guijson)?;
+
+    println!("sending OSC to {}", target);
+    Ok(Osc {
+      socket,
+      target,
+      only: prefs.mode == Some(BL::OscMode::Only),
+      address: prefs
+        .address
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+      scales: prefs.scale.clone().unwrap_or_default(),
+      labels,
+    })
+  }
+
+  // a profile switch or reload brought in other controls.
+  pub fn load_gui(&mut self, guijson: &str) {
+    match labels(guijson) {
+      Ok(l) => self.labels = l,
+      Err(e) => println!("error reading OSC labels: {}", e),
+    }
+  }
+
+  // true if OSC replaces the usual keys and mouse.
+  pub fn only(&self) -> bool {
+    self.only
+  }
+
+  pub fn on_update(&self, update: &cu::UpdateMsg) {
+    match update {
+      cu::UpdateMsg::Button {
+        control_id,
+        state: Some(state),
+        ..
+      } => {
+        let v = match state {
+          cu::PressState::Pressed => 1.0,
+          cu::PressState::Unpressed => 0.0,
+        };
+        self.send("button", control_id, &[v]);
+      }
+      cu::UpdateMsg::Slider {
+        control_id,
+        location: Some(loc),
+        ..
+      } => self.send("slider", control_id, &[*loc as f32]),
+      cu::UpdateMsg::XY {
+        control_id,
+        location: Some((x, y)),
+        ..
+      } => self.send("xy", control_id, &[*x, *y]),
+      _ => (),
+    }
+  }
+
+  fn address_for(&self, kind: &str, control_id: &[i32]) -> String {
+    let id: Vec<String> = control_id.iter().map(|i| i.to_string()).collect();
+    let id = id.join("/");
+    let label = match self.labels.get(control_id) {
+      Some(l) => l.clone(),
+      None => id.clone(),
+    };
+    self
+      .address
+      .replace("{kind}", kind)
+      .replace("{label}", label.as_str())
+      .replace("{id}", id.as_str())
+  }
+
+  // values are 0.0 to 1.0, scaled by the first matching OscScale.
+  fn send(&self, kind: &str, control_id: &[i32], values: &[f32]) {
+    let address = self.address_for(kind, control_id);
+    let scale = self
+      .scales
+      .iter()
+      .find(|s| matches(s.address.as_str(), address.as_str()));
+    let arguments = values
+      .iter()
+      .map(|v| match scale {
+        Some(s) => {
+          let scaled = s.min + v * (s.max - s.min);
+          if s.integer.unwrap_or(false) {
+            Argument::i(scaled.round() as i32)
+          } else {
+            Argument::f(scaled)
+          }
+        }
+        None => Argument::f(*v),
+      })
+      .collect();
+    let msg = Message {
+      path: address.as_str(),
+      arguments,
+    };
+    match msg.serialize() {
+      Ok(bytes) => match self.socket.send_to(&bytes, self.target) {
+        Ok(_) => (),
+        Err(e) => println!("error sending OSC to {}: {}", self.target, e),
+      },
+      Err(e) => println!("error making OSC message for {}: {}", address, e),
+    }
+  }
+}
+
+fn labels(guijson: &str) -> Result<HashMap<Vec<i32>, String>, FError> {
+  let root = J::deserialize_root(&serde_json::from_str(guijson)?)?;
+  let mut labels = HashMap::new();
+  for (id, c) in controls::make_control_map(&*root.root_control) {
+    match c.as_json().get("label").and_then(|l| l.as_str()) {
+      Some(l) if !l.is_empty() => {
+        labels.insert(id, clean(l));
+      }
+      _ => (),
+    }
+  }
+  Ok(labels)
+}
+
+// an exact match, or a prefix ending in *.
+fn matches(pattern: &str, address: &str) -> bool {
+  match pattern.strip_suffix('*') {
+    Some(prefix) => address.starts_with(prefix),
+    None => pattern == address,
+  }
+}
+
+// labels go in addresses, so no spaces or OSC pattern characters.
+fn clean(label: &str) -> String {
+  label
+    .chars()
+    .map(|c| match c {
+      ' ' | '#' | '*' | ',' | '/' | '?' | '[' | ']' | '{' | '}' => '_',
+      c => c,
+    })
+    .collect()
+}
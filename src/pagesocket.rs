@@ -0,0 +1,155 @@
+// ---------------------------------------------------------------
+// the page's websocket, served on connections the web server has
+// already checked against allowed_clients and the token.  this does
+// what touchpage's websocket server does, but without a loopback port
+// that any local user could connect to and skip those checks.
+// ---------------------------------------------------------------
+use crate::api;
+use crate::held;
+use crate::server;
+use failure::err_msg;
+use failure::Error as FError;
+use serde_json::Value;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex, MutexGuard};
+use touchpage::control_nexus::ControlNexus;
+use touchpage::controls;
+use touchpage::json;
+use websocket::sync::server::IntoWs;
+use websocket::sync::Writer;
+use websocket::{Message, OwnedMessage, WebSocketError};
+
+// what the page asks for.
+const PROTOCOL: &str = "rust-websocket";
+
+type Sender = Arc<Mutex<Writer<TcpStream>>>;
+
+fn lock(sender: &Sender) -> MutexGuard<'_, Writer<TcpStream>> {
+  match sender.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+// do the websocket handshake on a connection whose upgrade request hasn't
+// been read yet, then pass messages to and from the page until it goes
+// away.
+pub fn serve(
+  stream: TcpStream,
+  api: &api::Api,
+  held: &held::SharedHeld,
+  id: held::ClientId,
+) -> Result<(), FError> {
+  let upgrade = match stream.into_ws() {
+    Ok(u) => u,
+    Err((mut stream, _, _, e)) => {
+      let msg = "bad websocket request";
+      server::write_response(
+        &mut stream,
+        "400 Bad Request",
+        "text/plain",
+        msg.len(),
+        msg.as_bytes(),
+      )?;
+      return Err(err_msg(format!("{}: {}", msg, e)));
+    }
+  };
+  if !upgrade.protocols().iter().any(|p| p == PROTOCOL) {
+    let _ = upgrade.reject();
+    return Err(err_msg(format!("websocket request without {}", PROTOCOL)));
+  }
+  let mut client = upgrade
+    .use_protocol(PROTOCOL)
+    .accept()
+    .map_err(|(_, e)| e)?;
+  let ip = client.peer_addr()?;
+
+  // the page builds itself from the gui and the controls' state.
+  let state = {
+    let ci = match api.cn.ci.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut gui: Value = serde_json::from_str(ci.guijson.as_str())?;
+    let updates = controls::cm_to_update_array(&ci.cm)
+      .iter()
+      .map(json::encode_update_message)
+      .collect();
+    if let Some(o) = gui.as_object_mut() {
+      o.insert("state".to_string(), Value::Array(updates));
+    }
+    gui.to_string()
+  };
+  client.send_message(&Message::text(state))?;
+
+  let (mut receiver, sender) = client.split()?;
+  let sender = Arc::new(Mutex::new(sender));
+  let mut cn = api.cn.clone();
+  cn.bc.register(sender.clone());
+
+  for message in receiver.incoming_messages() {
+    let message = match message {
+      Ok(m) => m,
+      // the page went away without saying goodbye.
+      Err(WebSocketError::NoDataAvailable) => return Ok(()),
+      Err(e) => return Err(e.into()),
+    };
+    match message {
+      OwnedMessage::Close(_) => {
+        lock(&sender).send_message(&OwnedMessage::Close(None))?;
+        return Ok(());
+      }
+      OwnedMessage::Ping(ping) => lock(&sender).send_message(&OwnedMessage::Pong(ping))?,
+      OwnedMessage::Text(text) => on_text(text, &ip, &mut cn, &api.processor, held, id),
+      other => println!("unrecognized websocket message: {:?}", other),
+    }
+  }
+  Ok(())
+}
+
+// update the control, tell the other pages, and do whatever the control
+// does.
+fn on_text(
+  text: String,
+  ip: &SocketAddr,
+  cn: &mut ControlNexus,
+  processor: &api::Processor,
+  held: &held::SharedHeld,
+  id: held::ClientId,
+) {
+  let value = match serde_json::from_str::<Value>(text.as_str()) {
+    Ok(v) => v,
+    Err(e) => {
+      println!("bad websocket message: {}", e);
+      return;
+    }
+  };
+  let update = match json::decode_update_message(&value) {
+    Some(u) => u,
+    None => {
+      println!("unknown update message: {}", text);
+      return;
+    }
+  };
+  {
+    let mut ci = match cn.ci.lock() {
+      Ok(guard) => guard,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    match ci.cm.get_mut(controls::get_um_id(&update)) {
+      Some(control) => {
+        control.update(&update);
+        cn.bc.broadcast_others(ip, Message::text(text.clone()));
+      }
+      None => println!("update for unknown control: {}", text),
+    }
+  }
+  let mut p = match processor.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  };
+  // whatever this presses is this client's.
+  held::lock(held).client = id;
+  p.on_update_received(&update, cn);
+  held::lock(held).client = 0;
+}
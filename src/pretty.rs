@@ -0,0 +1,267 @@
+// ---------------------------------------------------------------
+// indented s-expression output for prefs files.  lists that fit on
+// the line stay on one line, otherwise their elements line up under
+// the first one, like the configs in the configs directory:
+//
+//   ((prefs (xmult . 1000.0)
+//           (ymult . 1000.0)
+//           ...)
+//
+// optionally the first use of each field or control gets a comment
+// saying what it's for.
+// ---------------------------------------------------------------
+use failure::Error as FError;
+use serde::Serialize;
+use serde_lexpr::Value;
+use std::collections::HashSet;
+
+const WIDTH: usize = 80;
+
+struct Line {
+  text: String,
+  comment: Option<String>,
+}
+
+struct Printer {
+  comments: bool,
+  // keys that have been commented already.
+  commented: HashSet<String>,
+}
+
+pub fn to_string<T: Serialize>(value: &T, comments: bool) -> Result<String, FError> {
+  let v = serde_lexpr::to_value(value)?;
+  let mut p = Printer {
+    comments,
+    commented: HashSet::new(),
+  };
+  let mut out = String::new();
+  for l in p.render(&v, 0) {
+    out.push_str(l.text.as_str());
+    if let Some(c) = l.comment {
+      out.push_str("  ; ");
+      out.push_str(c.as_str());
+    }
+    out.push('\n');
+  }
+  Ok(out)
+}
+
+// f32s come through as f64s, so 0.1 would be 0.10000000149011612.  print
+// the shortest thing that reads back the same.
+fn float(f: f64) -> String {
+  let s = if (f as f32) as f64 == f {
+    format!("{}", f as f32)
+  } else {
+    format!("{}", f)
+  };
+  if f.is_finite() && !s.contains('.') && !s.contains('e') {
+    format!("{}.0", s)
+  } else {
+    s
+  }
+}
+
+fn atom(v: &Value) -> String {
+  match v {
+    Value::Number(n) if n.is_f64() => float(n.as_f64().unwrap_or(0.0)),
+    Value::Null | Value::Nil => "()".to_string(),
+    _ => v.to_string(),
+  }
+}
+
+// the whole thing on one line.
+fn flat(v: &Value) -> String {
+  match v.as_cons() {
+    Some(c) => {
+      let (es, tail) = c.to_ref_vec();
+      let mut s = "(".to_string();
+      s.push_str(
+        es.iter()
+          .map(|e| flat(e))
+          .collect::<Vec<String>>()
+          .join(" ")
+          .as_str(),
+      );
+      if !(tail.is_nil() || tail.is_null()) {
+        s.push_str(" . ");
+        s.push_str(atom(tail).as_str());
+      }
+      s.push(')');
+      s
+    }
+    None => atom(v),
+  }
+}
+
+fn key_of(v: &Value) -> Option<&str> {
+  v.as_cons().and_then(|c| c.car().as_symbol())
+}
+
+impl Printer {
+  fn comment_for(&mut self, v: &Value) -> Option<String> {
+    if !self.comments {
+      return None;
+    }
+    let key = key_of(v)?;
+    let c = comment(key)?;
+    if self.commented.insert(key.to_string()) {
+      Some(c.to_string())
+    } else {
+      None
+    }
+  }
+
+  // true if printing v would add any comments.
+  fn has_comments(&self, v: &Value) -> bool {
+    if !self.comments {
+      return false;
+    }
+    match v.as_cons() {
+      Some(c) => {
+        key_of(v)
+          .map(|k| comment(k).is_some() && !self.commented.contains(k))
+          .unwrap_or(false)
+          || self.has_comments(c.car())
+          || self.has_comments(c.cdr())
+      }
+      None => false,
+    }
+  }
+
+  // lines for v, starting at column col.  lines after the first are
+  // indented already.
+  fn render(&mut self, v: &Value, col: usize) -> Vec<Line> {
+    let c = match v.as_cons() {
+      Some(c) => c,
+      None => {
+        return vec![Line {
+          text: atom(v),
+          comment: None,
+        }]
+      }
+    };
+    let (es, tail) = c.to_ref_vec();
+    let f = flat(v);
+    let dotted = !(tail.is_nil() || tail.is_null());
+    // v's own comment can follow it, but comments inside need lines.
+    let inner = es.iter().any(|e| self.has_comments(e));
+    if dotted || (col + f.len() <= WIDTH && !inner) {
+      let comment = self.comment_for(v);
+      return vec![Line { text: f, comment }];
+    }
+    let comment = self.comment_for(v);
+
+    // leading symbols stay with the open paren: (control Sizer ...
+    let heads = es.iter().take_while(|e| e.as_cons().is_none()).count();
+    let mut first = "(".to_string();
+    first.push_str(
+      es[..heads]
+        .iter()
+        .map(|e| atom(e))
+        .collect::<Vec<String>>()
+        .join(" ")
+        .as_str(),
+    );
+    let argcol = if heads == 0 {
+      col + 1
+    } else {
+      col + first.len() + 1
+    };
+    let mut lines = vec![Line {
+      text: first,
+      comment,
+    }];
+    for (i, e) in es[heads..].iter().enumerate() {
+      let mut sub = self.render(e, argcol).into_iter();
+      match sub.next() {
+        // the first element shares the line, unless that line already has
+        // this list's comment.
+        Some(l) if i == 0 && lines[0].comment.is_none() => {
+          if heads > 0 {
+            lines[0].text.push(' ');
+          }
+          lines[0].text.push_str(l.text.as_str());
+          lines[0].comment = l.comment;
+        }
+        Some(l) => lines.push(Line {
+          text: format!("{}{}", " ".repeat(argcol), l.text),
+          comment: l.comment,
+        }),
+        None => (),
+      }
+      lines.extend(sub);
+    }
+
+    // a comment runs to the end of the line, so the close paren goes
+    // on the next one.
+    let last = lines.len() - 1;
+    if lines[last].comment.is_some() {
+      lines.push(Line {
+        text: format!("{})", " ".repeat(col)),
+        comment: None,
+      });
+    } else {
+      lines[last].text.push(')');
+    }
+    lines
+  }
+}
+
+// explanations for fields and controls.
+fn comment(key: &str) -> Option<&'static str> {
+  match key {
+    "prefs" => Some("prefs section for ports and some mouse feel params."),
+    "xmult" => Some("mouse speed, horizontal"),
+    "ymult" => Some("mouse speed, vertical"),
+    "max_tap_duration" => Some("milliseconds; shorter touches are clicks"),
+    "show_press_duration" => Some("use #t and #f for true/false"),
+    "scroll_threshold" => Some("movement per scroll step when scrolling"),
+    "html_port" => Some("port for the control web page"),
+    "websocket_port" => Some("port for the control page's websocket"),
+    "mdns" => Some("announce the page on the local network, (mdns #f) to stop"),
+    "mdns_interfaces" => Some("interfaces to announce on, like (\"eth0\")"),
+    "single_port" => Some("#t serves page and websocket on html_port"),
+    "base_path" => Some("url path prefix, for running behind a proxy"),
+    "public_websocket_url" => Some("websocket url as seen by browsers"),
+    "trusted_proxies" => Some("X-Forwarded-For from these is believed"),
+    "allowed_clients" => Some("optional; only these clients may connect"),
+    "release_timeout" => Some("ms idle before held keys are let go; off if empty"),
+    "mpris_player" => Some("media player for Mpris actions, like \"vlc\""),
+    "mpris_label" => Some("name of a Label to show the current track in"),
+    "osc" => Some("send OSC to host and port; see README.md"),
+    "midi_port" => Some("name of the virtual MIDI port, \"mousepage\" if empty"),
+    "control_socket" => Some("unix socket for scripts; see README.md"),
+    "profiles" => Some("prefs files to switch to, by name"),
+    "token" => Some("clients must give this; see README.md"),
+    "window_provider" => Some("X11, or (File . \"<path>\"); see README.md"),
+    "follow_window" => Some("switch profiles to follow the focused program"),
+    "gui" => Some("gui section has 'title', 'control' and 'colors'."),
+    "title" => Some("title bar of the web page."),
+    "control" => Some("top control is typically a Sizer."),
+    "colors" => Some("see buildlisp.rs for a list of valid 'color' symbols."),
+    "Sizer" => Some("a row or column of controls"),
+    "Grid" => Some("rows x columns of cells; controls can span cells"),
+    "orientation" => Some("Horizontal or Vertical"),
+    "controls" => Some("list of controls"),
+    "proportion" => Some("size relative to siblings; empty for an even share"),
+    "Label" => Some("labels show text, fixed or from a source."),
+    "source" => Some("Command, File, Clock, ActiveWindow or Battery"),
+    "interval" => Some("milliseconds between reads of the source"),
+    "MouseButton" => Some("button is LeftButton, RightButton or MiddleButton"),
+    "ScrollButton" => Some("hold to make MouseXy scroll instead"),
+    "MouseXy" => Some("moves the mouse around like a touchpad."),
+    "Key" => Some("keys are pressed left to right, released right to left"),
+    "keys" => Some("see buildlisp.rs for valid key symbols"),
+    "Jog" => Some("turn to tap keys, steps_per_revolution per turn"),
+    "Joystick" => Some("drag from the center to move the mouse or hold keys"),
+    "Keyboard" => Some("a whole keyboard in one control"),
+    "Http" => Some("method, url, headers and body; see README.md"),
+    "Command" => Some("program and args; see README.md for the rest"),
+    "AppKeys" => Some("keys for the program in front; see README.md"),
+    "Midi" => Some("channel 1 to 16; see README.md"),
+    "MidiXy" => Some("x and y send MIDI control changes"),
+    "Slider" => Some("action runs as the slider moves"),
+    "Toggle" => Some("alternates between the on and off actions"),
+    _ => None,
+  }
+}
@@ -0,0 +1,196 @@
+// ---------------------------------------------------------------
+// switching the page to another prefs file's gui while running, and
+// reloading the current one.  only the gui and colors come from the
+// new file; ports and the rest of the prefs stay as they were at
+// startup.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::formats;
+use crate::held;
+use crate::midi;
+use crate::sources;
+use failure::err_msg;
+use failure::Error as FError;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use touchpage::control_nexus::ControlNexus;
+use touchpage::controls;
+
+pub struct Profiles {
+  cn: ControlNexus,
+  held: held::SharedHeld,
+  midi: midi::Midi,
+  // prefs files by profile name.
+  profiles: BTreeMap<String, String>,
+  // relative profile paths are from the startup prefs file's directory.
+  dir: PathBuf,
+  // prefs file the gui is from, if any, and its profile name.
+  file: Option<String>,
+  name: Option<String>,
+  // colors set through the api, on top of the file's.
+  colors: Vec<BL::SetColor>,
+  sources: sources::Sources,
+  // counts gui loads, so the update processor knows to forget the old
+  // controls.
+  gui_loads: Arc<AtomicUsize>,
+}
+
+pub type SharedProfiles = Arc<Mutex<Profiles>>;
+
+pub fn lock(profiles: &SharedProfiles) -> MutexGuard<'_, Profiles> {
+  match profiles.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+impl Profiles {
+  pub fn new(
+    cn: ControlNexus,
+    held: held::SharedHeld,
+    midi: midi::Midi,
+    profiles: BTreeMap<String, String>,
+    file: Option<String>,
+    sources: sources::Sources,
+    gui_loads: Arc<AtomicUsize>,
+  ) -> SharedProfiles {
+    let dir = match &file {
+      Some(f) => Path::new(f)
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default(),
+      None => PathBuf::new(),
+    };
+    Arc::new(Mutex::new(Profiles {
+      cn,
+      held,
+      midi,
+      profiles,
+      dir,
+      file,
+      name: None,
+      colors: Vec::new(),
+      sources,
+      gui_loads,
+    }))
+  }
+
+  // name of the current profile, if it was switched to by name.
+  pub fn name(&self) -> Option<String> {
+    self.name.clone()
+  }
+
+  pub fn is_named(&self, name: &str) -> bool {
+    self.profiles.contains_key(name)
+  }
+
+  pub fn names(&self) -> Vec<String> {
+    self.profiles.keys().cloned().collect()
+  }
+
+  // switch to a profile from the prefs, or failing that a prefs file.
+  pub fn switch(&mut self, name: &str) -> Result<(), FError> {
+    if self.name.as_deref() == Some(name) {
+      return Ok(());
+    }
+    let file = match self.profiles.get(name) {
+      Some(f) => f.clone(),
+      None => name.to_string(),
+    };
+    let path = self.dir.join(file.as_str());
+    let path = path.to_string_lossy().to_string();
+    self.load(path.as_str())?;
+    println!("switched to profile {}", name);
+    self.file = Some(path);
+    self.name = Some(name.to_string());
+    Ok(())
+  }
+
+  // read the current prefs file again.
+  pub fn reload(&mut self) -> Result<(), FError> {
+    match self.file.clone() {
+      Some(f) => {
+        self.load(f.as_str())?;
+        println!("reloaded {}", f);
+        Ok(())
+      }
+      None => Err(err_msg("no prefs file to reload")),
+    }
+  }
+
+  // these colors replace the file's until mousepage restarts.  they go
+  // on the gui as it is, so this works without a prefs file too.
+  pub fn set_colors(&mut self, colors: Vec<BL::SetColor>) -> Result<(), FError> {
+    self.recolor(&colors)?;
+    for c in colors {
+      self.colors.retain(|sc| sc.color != c.color);
+      self.colors.push(c);
+    }
+    Ok(())
+  }
+
+  // the same gui and control state, other colors.
+  fn recolor(&self, colors: &[BL::SetColor]) -> Result<(), FError> {
+    let (guijson, state) = {
+      let ci = match self.cn.ci.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+      };
+      (ci.guijson.clone(), controls::cm_to_update_array(&ci.cm))
+    };
+    let mut gui: Value = serde_json::from_str(guijson.as_str())?;
+    match gui.as_object_mut() {
+      Some(o) => {
+        for c in colors {
+          o.insert(
+            color_key(&c.color).to_string(),
+            Value::String(c.hexstring.clone()),
+          );
+        }
+      }
+      None => return Err(err_msg("the gui isn't a json object")),
+    }
+    self.cn.load_gui_string(gui.to_string().as_str())?;
+    for u in state.iter() {
+      self.cn.update(u);
+    }
+    Ok(())
+  }
+
+  fn load(&mut self, file: &str) -> Result<(), FError> {
+    let s = fs::read_to_string(file).map_err(|e| err_msg(format!("{}: {}", file, e)))?;
+    let mut settings = formats::parse_settings(s.as_str(), file)?;
+    let mut colors = settings.colors.unwrap_or(vec![]);
+    for c in self.colors.iter() {
+      colors.retain(|sc| sc.color != c.color);
+      colors.push(c.clone());
+    }
+    if BL::uses_midi(&settings.gui.control) && !self.midi.is_open() {
+      self.midi.open();
+    }
+    let sourced_labels = BL::sourced_labels(&mut settings.gui.control);
+    let guijson = BL::gui_json(settings.gui, colors)?;
+    // anything held down belongs to controls that are going away.
+    held::lock(&self.held).release_all();
+    self.cn.load_gui_string(guijson.as_str())?;
+    self.gui_loads.fetch_add(1, Ordering::SeqCst);
+    self.sources.start(sourced_labels);
+    Ok(())
+  }
+}
+
+// where touchpage keeps each color in the gui json.
+fn color_key(color: &BL::Color) -> &'static str {
+  match color {
+    BL::Color::Controls => "controlsColor",
+    BL::Color::Labels => "labelsColor",
+    BL::Color::Text => "textColor",
+    BL::Color::Pressed => "pressedColor",
+    BL::Color::Unpressed => "unpressedColor",
+    BL::Color::Background => "backgroundColor",
+  }
+}
@@ -0,0 +1,127 @@
+// ---------------------------------------------------------------
+// show the page url as a QR code so the phone can just scan it,
+// instead of hunting down the host's LAN ip and typing it in.
+// ---------------------------------------------------------------
+use crate::server;
+use failure::Error as FError;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::{Color, QrCode};
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::IpAddr;
+
+// pixels per QR module in the png, and blank modules around the code.
+const PNG_MODULE_SIZE: u32 = 8;
+const PNG_QUIET_ZONE: u32 = 4;
+
+// non-loopback addresses of this host, ipv4 first.  link-local ipv6
+// addresses are skipped since a browser can't use them without a scope id.
+pub fn lan_addresses() -> Vec<IpAddr> {
+  let mut addrs: Vec<IpAddr> = match if_addrs::get_if_addrs() {
+    Ok(ifaces) => ifaces
+      .iter()
+      .filter(|i| !i.is_loopback())
+      .map(|i| i.ip())
+      .filter(|ip| match ip {
+        IpAddr::V4(_) => true,
+        IpAddr::V6(v6) => (v6.segments()[0] & 0xffc0) != 0xfe80,
+      })
+      .collect(),
+    Err(e) => {
+      println!("error getting network interfaces: {}", e);
+      Vec::new()
+    }
+  };
+  addrs.sort_by_key(|ip| ip.is_ipv6());
+  addrs.dedup();
+  addrs
+}
+
+pub fn page_url(ip: &IpAddr, html_port: i32, base_path: &str, token: Option<&str>) -> String {
+  let url = match ip {
+    IpAddr::V4(_) => format!("http://{}:{}{}", ip, html_port, base_path),
+    IpAddr::V6(_) => format!("http://[{}]:{}{}", ip, html_port, base_path),
+  };
+  match token {
+    Some(t) if !t.is_empty() => format!("{}?token={}", url, server::percent_encode(t)),
+    _ => url,
+  }
+}
+
+// QR code as a string of unicode half blocks, two modules per character.
+// colors are inverted so the code reads correctly on a dark terminal.
+pub fn qr_string(url: &str) -> Result<String, FError> {
+  let code = QrCode::new(url.as_bytes())?;
+  Ok(
+    code
+      .render::<Dense1x2>()
+      .dark_color(Dense1x2::Light)
+      .light_color(Dense1x2::Dark)
+      .quiet_zone(true)
+      .build(),
+  )
+}
+
+// write the QR code as a black on white grayscale png.
+pub fn write_qr_png(url: &str, file_name: &str) -> Result<(), FError> {
+  let code = QrCode::new(url.as_bytes())?;
+  let modules = code.width() as u32;
+  let colors = code.to_colors();
+  let size = (modules + 2 * PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+
+  let mut pixels = vec![255u8; (size * size) as usize];
+  for y in 0..modules {
+    for x in 0..modules {
+      if colors[(y * modules + x) as usize] == Color::Dark {
+        let px = (x + PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+        let py = (y + PNG_QUIET_ZONE) * PNG_MODULE_SIZE;
+        for row in py..py + PNG_MODULE_SIZE {
+          let start = (row * size + px) as usize;
+          for p in &mut pixels[start..start + PNG_MODULE_SIZE as usize] {
+            *p = 0;
+          }
+        }
+      }
+    }
+  }
+
+  let file = File::create(file_name)?;
+  let mut encoder = png::Encoder::new(BufWriter::new(file), size, size);
+  encoder.set_color(png::ColorType::Grayscale);
+  encoder.set_depth(png::BitDepth::Eight);
+  let mut writer = encoder.write_header()?;
+  writer.write_image_data(&pixels)?;
+  Ok(())
+}
+
+// print the page urls for all LAN addresses, with a QR code for the first
+// one.  optionally write that QR code to a png too.
+pub fn show_page_url(html_port: i32, base_path: &str, token: Option<&str>, png_file: Option<&str>) {
+  let addrs = lan_addresses();
+  let url = match addrs.first() {
+    Some(ip) => page_url(ip, html_port, base_path, token),
+    None => {
+      println!("no LAN address found; is the network up?");
+      return;
+    }
+  };
+
+  match qr_string(url.as_str()) {
+    Ok(s) => println!("{}", s),
+    Err(e) => println!("error making QR code: {}", e),
+  }
+
+  for ip in addrs.iter() {
+    println!(
+      "control page: {}",
+      page_url(ip, html_port, base_path, token)
+    );
+  }
+
+  if let Some(f) = png_file {
+    match write_qr_png(url.as_str(), f) {
+      Ok(_) => println!("wrote QR code to {}", f),
+      Err(e) => println!("error writing QR code png: {}", e),
+    }
+  }
+}
@@ -0,0 +1,601 @@
+// ---------------------------------------------------------------
+// http front end.  serves the control page, and the page's websocket
+// through pagesocket.  In single port mode the page and the websocket
+// share html_port; otherwise websocket_port is used for the websocket
+// like before.
+// ---------------------------------------------------------------
+use crate::access::Access;
+use crate::api;
+use crate::held;
+use crate::pagesocket;
+use failure::err_msg;
+use failure::Error as FError;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use touchpage::string_defaults;
+
+// biggest request head we'll accept before giving up on a client.
+const MAX_HEAD_SIZE: usize = 16384;
+// and the biggest api request body.
+const MAX_BODY_SIZE: usize = 65536;
+// how long a client gets to send its request head, and for each read
+// after that, so stalled clients don't hold threads forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// the page sets this so its websocket has the token too.
+const TOKEN_COOKIE: &str = "mousepage_token";
+
+// ids for websocket clients.  0 is for the api.
+static NEXT_CLIENT: AtomicUsize = AtomicUsize::new(1);
+
+pub struct ServerConfig {
+  pub ip: String,
+  pub html_port: i32,
+  pub websocket_port: i32,
+  pub single_port: bool,
+  // path the page is served under, with leading and trailing '/'.
+  pub base_path: String,
+  // websocket url for the page, if the default won't do.  may be relative
+  // to the page.
+  pub public_websocket_url: Option<String>,
+  pub access: Access,
+  // released when a client disconnects.
+  pub held: held::SharedHeld,
+  pub clients: SharedClients,
+  // clients must give this, if set.
+  pub token: Option<String>,
+  // the http api, under base_path/api/, and what the websocket talks to.
+  pub api: Option<api::Api>,
+}
+
+// a page connected to the websocket.
+pub struct Client {
+  pub id: held::ClientId,
+  // the client's address, and the proxy's if it came through one.
+  pub from: String,
+  pub since: Instant,
+}
+
+pub type SharedClients = Arc<Mutex<Vec<Client>>>;
+
+pub fn lock_clients(clients: &SharedClients) -> MutexGuard<'_, Vec<Client>> {
+  match clients.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+impl ServerConfig {
+  // the port the browser should open the websocket on.
+  pub fn public_websocket_port(&self) -> i32 {
+    if self.single_port {
+      self.html_port
+    } else {
+      self.websocket_port
+    }
+  }
+
+  // the url the page opens its websocket on, or None to let the page
+  // use the websocket port on the page's host.  in single port mode that's
+  // the page url itself, which works through reverse proxies and https.
+  pub fn page_websocket_url(&self) -> Option<String> {
+    match &self.public_websocket_url {
+      Some(u) => Some(u.clone()),
+      None => {
+        if self.single_port {
+          Some(self.base_path.clone())
+        } else {
+          None
+        }
+      }
+    }
+  }
+
+  // does the request path fall under base_path?
+  pub fn in_base_path(&self, path: &str) -> bool {
+    let path = path.split('?').next().unwrap_or("");
+    path.starts_with(self.base_path.as_str()) || path == self.base_path.trim_end_matches('/')
+  }
+
+  // the part of an api request's path after base_path/api/.
+  pub fn api_route(&self, path: &str) -> Option<String> {
+    let path = path.split('?').next().unwrap_or("");
+    path
+      .strip_prefix(self.base_path.as_str())
+      .and_then(|p| p.strip_prefix("api/"))
+      .map(|r| r.to_string())
+  }
+
+  pub fn has_token(&self) -> bool {
+    match &self.token {
+      Some(t) => !t.is_empty(),
+      None => false,
+    }
+  }
+
+  // the token from the url, an Authorization header or the page's
+  // cookie.  anything goes if there's no token in the prefs.
+  pub fn token_ok(&self, head: &RequestHead) -> bool {
+    let token = match &self.token {
+      Some(t) if !t.is_empty() => t,
+      _ => return true,
+    };
+    let bearer = head
+      .header("authorization")
+      .and_then(|a| a.strip_prefix("Bearer "))
+      .map(|t| t.trim().to_string());
+    let cookie = head.header("cookie").and_then(|c| {
+      c.split(';').find_map(|kv| {
+        let kv = kv.trim();
+        kv.strip_prefix(TOKEN_COOKIE)
+          .and_then(|v| v.strip_prefix('='))
+          .map(percent_decode)
+      })
+    });
+    [query_param(head.path.as_str(), "token"), bearer, cookie]
+      .iter()
+      .any(|t| match t {
+        Some(t) => same(t.as_bytes(), token.as_bytes()),
+        None => false,
+      })
+  }
+}
+
+// compare without bailing at the first difference, so the time taken
+// doesn't give away how much of a guessed token was right.
+fn same(a: &[u8], b: &[u8]) -> bool {
+  a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |d, (x, y)| d | (x ^ y)) == 0
+}
+
+// a parameter from the url's query string, decoded.
+pub fn query_param(path: &str, name: &str) -> Option<String> {
+  let query = path.split_once('?')?.1;
+  query.split('&').find_map(|kv| {
+    let mut kv = kv.splitn(2, '=');
+    if kv.next() == Some(name) {
+      Some(percent_decode(kv.next().unwrap_or("")))
+    } else {
+      None
+    }
+  })
+}
+
+// everything but letters, digits and -._~ as %xx.
+pub fn percent_encode(s: &str) -> String {
+  s.bytes()
+    .map(|b| match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+        (b as char).to_string()
+      }
+      _ => format!("%{:02X}", b),
+    })
+    .collect()
+}
+
+// %xx escapes and + for space.
+pub fn percent_decode(s: &str) -> String {
+  let bytes = s.as_bytes();
+  let mut out = Vec::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'%' if i + 2 < bytes.len() => {
+        let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+        match u8::from_str_radix(hex, 16) {
+          Ok(b) => {
+            out.push(b);
+            i += 3;
+            continue;
+          }
+          Err(_) => out.push(b'%'),
+        }
+      }
+      b'+' => out.push(b' '),
+      b => out.push(b),
+    }
+    i += 1;
+  }
+  String::from_utf8_lossy(&out).to_string()
+}
+
+// base path from prefs, with leading and trailing slashes.
+pub fn normalize_base_path(p: Option<&String>) -> String {
+  match p {
+    Some(s) => {
+      let t = s.trim_matches('/');
+      if t.is_empty() {
+        "/".to_string()
+      } else {
+        format!("/{}/", t)
+      }
+    }
+    None => "/".to_string(),
+  }
+}
+
+struct Shared {
+  config: ServerConfig,
+  html: String,
+}
+
+// lets the page open its websocket somewhere other than ws://host:port.
+// relative urls are resolved against the page location, with http(s)
+// swapped for ws(s).
+const WEBSOCKET_URL_JS: &str = r##"var mySockets = {};
+
+  var mousepageWebsocket = {{mousepage-websocket-url}};
+  function mousepageWebsocketUrl(address) {
+    if (mousepageWebsocket === null) {
+      return address;
+    }
+    var url = new URL(mousepageWebsocket, document.location.href);
+    url.protocol = url.protocol.replace(/^http/, "ws");
+    return url.href;
+  }"##;
+
+// the touchpage page, pointed at our websocket.
+pub fn page_html(config: &ServerConfig) -> String {
+  let wsurl = serde_json::to_string(&config.page_websocket_url()).unwrap_or("null".to_string());
+  string_defaults::MAIN_HTML
+    .replace(
+      "{{websockets-port}}",
+      config.public_websocket_port().to_string().as_str(),
+    )
+    .replace(
+      "var mySockets = {};",
+      WEBSOCKET_URL_JS
+        .replace("{{mousepage-websocket-url}}", wsurl.as_str())
+        .as_str(),
+    )
+    .replace(
+      "new WebSocket(wat.address, wat.protocol)",
+      "new WebSocket(mousepageWebsocketUrl(wat.address), wat.protocol)",
+    )
+}
+
+// listen for page and websocket requests.  blocks.
+pub fn start(config: ServerConfig) -> Result<(), FError> {
+  let html = page_html(&config);
+  let shared = Arc::new(Shared { config, html });
+
+  if !shared.config.single_port {
+    let ws_addr = format!("{}:{}", shared.config.ip, shared.config.websocket_port);
+    let ws_listener = TcpListener::bind(ws_addr.as_str())?;
+    println!("websocket listening at {}", ws_addr);
+    let s = shared.clone();
+    thread::spawn(move || listen(ws_listener, s, true));
+  }
+
+  let html_addr = format!("{}:{}", shared.config.ip, shared.config.html_port);
+  let html_listener = TcpListener::bind(html_addr.as_str())?;
+  if shared.config.single_port {
+    println!("running web server and websocket at {}", html_addr);
+  } else {
+    println!("running web server at {}", html_addr);
+  }
+  listen(html_listener, shared, false);
+  Ok(())
+}
+
+// 'websocket_port' is for the websocket_port listener in two port mode.
+fn listen(listener: TcpListener, shared: Arc<Shared>, websocket_port: bool) {
+  for stream in listener.incoming() {
+    match stream {
+      Ok(s) => {
+        let sh = shared.clone();
+        thread::spawn(move || match handle_connection(s, &sh, websocket_port) {
+          Ok(_) => (),
+          Err(e) => println!("connection error: {}", e),
+        });
+      }
+      Err(e) => println!("error accepting connection: {}", e),
+    }
+  }
+}
+
+pub struct RequestHead {
+  pub method: String,
+  pub path: String,
+  pub headers: Vec<(String, String)>,
+}
+
+impl RequestHead {
+  pub fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(n, _)| n.eq_ignore_ascii_case(name))
+      .map(|(_, v)| v.as_str())
+  }
+
+  pub fn is_websocket_upgrade(&self) -> bool {
+    match self.header("upgrade") {
+      Some(u) => u.eq_ignore_ascii_case("websocket"),
+      None => false,
+    }
+  }
+}
+
+// find the end of the request head without taking it off the stream, so a
+// websocket upgrade can be handed on whole.  returns the head.
+fn peek_head(stream: &TcpStream) -> Result<Vec<u8>, FError> {
+  let mut buf = vec![0u8; MAX_HEAD_SIZE + 1];
+  let mut seen = 0;
+  let start = Instant::now();
+  loop {
+    let n = stream.peek(&mut buf)?;
+    if n == 0 {
+      return Err(err_msg("connection closed before end of request"));
+    }
+    match buf[..n].windows(4).position(|w| w == b"\r\n\r\n") {
+      Some(p) => {
+        buf.truncate(p + 4);
+        return Ok(buf);
+      }
+      None => {
+        if n > MAX_HEAD_SIZE {
+          return Err(err_msg("request head too large"));
+        }
+      }
+    }
+    // peek doesn't wait for more than it's already seen, so the read
+    // timeout only covers a client that sent nothing.
+    if n == seen {
+      if start.elapsed() > READ_TIMEOUT {
+        return Err(err_msg("timed out waiting for the request head"));
+      }
+      thread::sleep(Duration::from_millis(10));
+    }
+    seen = n;
+  }
+}
+
+// take what peek_head saw off the stream.
+fn skip(stream: &mut TcpStream, len: usize) -> Result<(), FError> {
+  let mut buf = vec![0u8; len];
+  stream.read_exact(&mut buf)?;
+  Ok(())
+}
+
+// a plain text error.  'unread' is how much of the request is still on the
+// stream; it's read first, since closing with it unread resets the
+// connection and the client may never see the reply.
+fn refuse(stream: &mut TcpStream, unread: usize, status: &str, msg: &str) -> Result<(), FError> {
+  skip(stream, unread)?;
+  write_response(stream, status, "text/plain", msg.len(), msg.as_bytes())
+}
+
+pub fn parse_head(head: &[u8]) -> Result<RequestHead, FError> {
+  let text = std::str::from_utf8(head)?;
+  let mut lines = text.split("\r\n");
+  let mut request_line = lines
+    .next()
+    .ok_or_else(|| err_msg("empty request"))?
+    .split(' ');
+  let method = request_line
+    .next()
+    .ok_or_else(|| err_msg("no request method"))?
+    .to_string();
+  let path = request_line
+    .next()
+    .ok_or_else(|| err_msg("no request path"))?
+    .to_string();
+
+  let mut headers = Vec::new();
+  for l in lines {
+    if let Some(i) = l.find(':') {
+      headers.push((l[..i].trim().to_string(), l[i + 1..].trim().to_string()));
+    }
+  }
+
+  Ok(RequestHead {
+    method,
+    path,
+    headers,
+  })
+}
+
+fn handle_connection(
+  mut stream: TcpStream,
+  shared: &Shared,
+  websocket_port: bool,
+) -> Result<(), FError> {
+  stream.set_read_timeout(Some(READ_TIMEOUT))?;
+  let buf = peek_head(&stream)?;
+  let head = parse_head(&buf)?;
+  let api_route = shared.config.api_route(head.path.as_str());
+  // the websocket handshake reads the upgrade request itself.  everything
+  // else has it read off the stream here.
+  let upgrade = head.is_websocket_upgrade() && api_route.is_none();
+  let unread = if upgrade {
+    buf.len()
+  } else {
+    skip(&mut stream, buf.len())?;
+    0
+  };
+
+  let peer = stream.peer_addr()?.ip();
+  let client = shared
+    .config
+    .access
+    .client_ip(peer, head.header("x-forwarded-for"));
+  let from = if client == peer {
+    format!("{}", client)
+  } else {
+    format!("{} (via {})", client, peer)
+  };
+
+  if !shared.config.access.is_allowed(&client) {
+    println!("refused {} {} from {}", head.method, head.path, from);
+    return refuse(&mut stream, unread, "403 Forbidden", "forbidden");
+  }
+
+  // the page opens its websocket on / of websocket_port, since it only
+  // knows the port.
+  if !(shared.config.in_base_path(head.path.as_str()) || (websocket_port && upgrade)) {
+    return refuse(&mut stream, unread, "404 Not Found", "not found");
+  }
+
+  if !shared.config.token_ok(&head) {
+    println!(
+      "wrong or missing token, refused {} {} from {}",
+      head.method, head.path, from
+    );
+    return refuse(&mut stream, unread, "401 Unauthorized", "unauthorized");
+  }
+
+  match (api_route, &shared.config.api) {
+    // anyone on the network could drive the host otherwise.
+    (Some(_), _) if !shared.config.has_token() => {
+      return refuse(
+        &mut stream,
+        unread,
+        "403 Forbidden",
+        "the http api is off without a token in the prefs",
+      );
+    }
+    (Some(route), Some(api)) => {
+      let body = read_body(&mut stream, &head)?;
+      if head.method != "GET" {
+        println!("api {} {} from {}", head.method, route, from);
+      }
+      let (status, reply) = api.rest(
+        head.method.as_str(),
+        route.as_str(),
+        head.path.as_str(),
+        body.as_slice(),
+      );
+      let reply = reply.to_string();
+      return write_response(
+        &mut stream,
+        status,
+        "application/json",
+        reply.len(),
+        reply.as_bytes(),
+      );
+    }
+    _ => (),
+  }
+
+  if upgrade {
+    let api = match &shared.config.api {
+      Some(api) => api,
+      None => {
+        return refuse(
+          &mut stream,
+          unread,
+          "503 Service Unavailable",
+          "websocket server not running",
+        )
+      }
+    };
+    // the page's websocket can be quiet for as long as it likes.
+    stream.set_read_timeout(None)?;
+    println!("websocket connection from {}", from);
+    let id = NEXT_CLIENT.fetch_add(1, Ordering::SeqCst);
+    lock_clients(&shared.config.clients).push(Client {
+      id,
+      from: from.clone(),
+      since: Instant::now(),
+    });
+    let r = pagesocket::serve(stream, api, &shared.config.held, id);
+    println!("websocket disconnected: {}", from);
+    lock_clients(&shared.config.clients).retain(|c| c.id != id);
+    // presses from a departed client will never be released otherwise.
+    held::lock(&shared.config.held).release_client(id);
+    r
+  } else if head.method == "GET" || head.method == "HEAD" {
+    println!("page request from {}", from);
+    let body = if head.method == "GET" {
+      shared.html.as_bytes()
+    } else {
+      &[]
+    };
+    // the websocket gets the token from the cookie, since the page
+    // doesn't know it.  on websocket_port the websocket is at /.
+    let cookie_path = if shared.config.single_port {
+      shared.config.base_path.as_str()
+    } else {
+      "/"
+    };
+    let cookie = match &shared.config.token {
+      Some(t) if !t.is_empty() => format!(
+        "Set-Cookie: {}={}; Path={}; HttpOnly; SameSite=Strict\r\n",
+        TOKEN_COOKIE,
+        percent_encode(t),
+        cookie_path
+      ),
+      _ => String::new(),
+    };
+    write_response_headers(
+      &mut stream,
+      "200 OK",
+      "text/html; charset=utf-8",
+      cookie.as_str(),
+      shared.html.len(),
+      body,
+    )
+  } else {
+    let msg = "method not allowed";
+    write_response(
+      &mut stream,
+      "405 Method Not Allowed",
+      "text/plain",
+      msg.len(),
+      msg.as_bytes(),
+    )
+  }
+}
+
+pub fn write_response(
+  stream: &mut TcpStream,
+  status: &str,
+  content_type: &str,
+  content_length: usize,
+  body: &[u8],
+) -> Result<(), FError> {
+  write_response_headers(stream, status, content_type, "", content_length, body)
+}
+
+// 'headers' are extra header lines, each ending in \r\n.
+pub fn write_response_headers(
+  stream: &mut TcpStream,
+  status: &str,
+  content_type: &str,
+  headers: &str,
+  content_length: usize,
+  body: &[u8],
+) -> Result<(), FError> {
+  write!(
+    stream,
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+    status, content_type, content_length, headers
+  )?;
+  stream.write_all(body)?;
+  stream.flush()?;
+  Ok(())
+}
+
+// the request body, as much as Content-Length says.
+fn read_body(stream: &mut TcpStream, head: &RequestHead) -> Result<Vec<u8>, FError> {
+  let len = match head.header("content-length") {
+    Some(l) => l.parse::<usize>()?,
+    None => 0,
+  };
+  if len > MAX_BODY_SIZE {
+    return Err(err_msg("request body too large"));
+  }
+  let mut body = Vec::new();
+  let mut chunk = [0u8; 2048];
+  while body.len() < len {
+    let n = stream.read(&mut chunk)?;
+    if n == 0 {
+      return Err(err_msg("connection closed before end of request body"));
+    }
+    body.extend_from_slice(&chunk[..n.min(len - body.len())]);
+  }
+  Ok(body)
+}
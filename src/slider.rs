@@ -0,0 +1,194 @@
+// ---------------------------------------------------------------
+// Slider control handling.  the slider's touchpage name is its
+// SliderAction as an s-expression, decoded here on each update.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::input;
+use crate::midi;
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::mpsc;
+use std::thread;
+use touchpage::control_updates as cu;
+
+// program, args and slider value.
+type Job = (String, Vec<String>, i32);
+
+pub struct SliderState {
+  // slider location as of the last step taken, per control.
+  last: HashMap<Vec<i32>, f64>,
+  // last value given to a command, per control.
+  last_value: HashMap<Vec<i32>, i32>,
+  // a worker per Command slider, so its commands run one at a time and
+  // the last one run has the slider's last value.
+  commands: HashMap<Vec<i32>, mpsc::Sender<Job>>,
+  midi: midi::Midi,
+}
+
+impl SliderState {
+  pub fn new(midi: midi::Midi) -> SliderState {
+    SliderState {
+      last: HashMap::new(),
+      last_value: HashMap::new(),
+      commands: HashMap::new(),
+      midi,
+    }
+  }
+
+  pub fn on_update(
+    &mut self,
+    action: &BL::SliderAction,
+    control_id: &Vec<i32>,
+    state: &Option<cu::PressState>,
+    location: &Option<f64>,
+  ) {
+    match action {
+      BL::SliderAction::Command { program, args } => {
+        if let Some(loc) = location {
+          let value = (loc * 100.0).round() as i32;
+          if self.last_value.get(control_id) != Some(&value) {
+            self.last_value.insert(control_id.clone(), value);
+            self.run(control_id, program, args, value);
+          }
+        }
+      }
+      BL::SliderAction::Midi {
+        channel,
+        cc,
+        min,
+        max,
+      } => {
+        if let Some(loc) = location {
+          self.midi.cc(*channel, *cc, *loc, *min, *max);
+        }
+      }
+      _ => {
+        match (state, location) {
+          (Some(cu::PressState::Pressed), Some(loc)) => {
+            self.last.insert(control_id.clone(), *loc);
+          }
+          (_, Some(loc)) => match self.last.get(control_id).cloned() {
+            Some(last) => {
+              let per_length = steps_per_length(action);
+              let steps = ((loc - last) * per_length).trunc();
+              if steps != 0.0 {
+                step(action, steps as i32);
+                self
+                  .last
+                  .insert(control_id.clone(), last + steps / per_length);
+              }
+            }
+            None => {
+              self.last.insert(control_id.clone(), *loc);
+            }
+          },
+          _ => (),
+        };
+        if let Some(cu::PressState::Unpressed) = state {
+          self.last.remove(control_id);
+        }
+      }
+    }
+  }
+
+  fn run(&mut self, control_id: &[i32], program: &str, args: &[String], value: i32) {
+    let job = (program.to_string(), args.to_vec(), value);
+    // a worker that died takes the job back, for a new one.
+    let job = match self.commands.get(control_id) {
+      Some(tx) => match tx.send(job) {
+        Ok(()) => return,
+        Err(mpsc::SendError(job)) => job,
+      },
+      None => job,
+    };
+    let (tx, rx) = mpsc::channel::<Job>();
+    thread::spawn(move || {
+      while let Ok(mut job) = rx.recv() {
+        // the slider moved on while the last command ran; only where it
+        // is now matters.
+        while let Ok(newer) = rx.try_recv() {
+          job = newer;
+        }
+        run_command(&job.0, &job.1, job.2);
+      }
+    });
+    let _ = tx.send(job);
+    self.commands.insert(control_id.to_vec(), tx);
+  }
+}
+
+// how many steps moving the slider end to end makes.
+fn steps_per_length(action: &BL::SliderAction) -> f64 {
+  match action {
+    BL::SliderAction::Scroll {
+      orientation: _,
+      ticks,
+    } => *ticks as f64,
+    BL::SliderAction::KeyPair {
+      increase: _,
+      decrease: _,
+      steps,
+      presses: _,
+    } => *steps as f64,
+    BL::SliderAction::Mouse {
+      orientation: _,
+      pixels,
+    } => *pixels as f64,
+    BL::SliderAction::Command {
+      program: _,
+      args: _,
+    } => 100.0,
+    BL::SliderAction::Midi {
+      channel: _,
+      cc: _,
+      min: _,
+      max: _,
+    } => 127.0,
+  }
+}
+
+fn step(action: &BL::SliderAction, steps: i32) {
+  match action {
+    BL::SliderAction::Scroll {
+      orientation,
+      ticks: _,
+    } => input::scroll(orientation, steps),
+    BL::SliderAction::KeyPair {
+      increase,
+      decrease,
+      steps: _,
+      presses,
+    } => {
+      let keys = if steps > 0 { increase } else { decrease };
+      input::tap_keys(keys, steps.unsigned_abs() * presses.unwrap_or(1));
+    }
+    BL::SliderAction::Mouse {
+      orientation,
+      pixels: _,
+    } => input::move_mouse(orientation, steps),
+    BL::SliderAction::Command {
+      program: _,
+      args: _,
+    } => (),
+    BL::SliderAction::Midi {
+      channel: _,
+      cc: _,
+      min: _,
+      max: _,
+    } => (),
+  }
+}
+
+// run the command and wait for it; "{value}" in the args becomes the
+// slider value, 0 to 100.
+fn run_command(program: &str, args: &[String], value: i32) {
+  let vs = value.to_string();
+  let args: Vec<String> = args
+    .iter()
+    .map(|a| a.replace("{value}", vs.as_str()))
+    .collect();
+  match Command::new(program).args(&args).status() {
+    Ok(_) => (),
+    Err(e) => println!("error running {}: {}", program, e),
+  }
+}
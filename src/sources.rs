@@ -0,0 +1,135 @@
+// ---------------------------------------------------------------
+// keeping Labels with a LabelSource up to date.  each one gets a
+// thread that reads its source every so often and sends the text to
+// the page when it changes.  switching profiles starts a new set of
+// threads, and the old ones stop.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use crate::commands;
+use crate::window;
+use std::fmt::Write;
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use touchpage::control_nexus::ControlNexus;
+
+const DEFAULT_INTERVAL: u64 = 1000;
+// a Command source that hangs is killed after this long, so the label
+// keeps going.
+const DEFAULT_TIMEOUT: u64 = 10000;
+
+#[derive(Clone)]
+pub struct Sources {
+  cn: ControlNexus,
+  provider: window::SharedProvider,
+  // bumped to stop the current threads.
+  generation: Arc<AtomicUsize>,
+}
+
+impl Sources {
+  pub fn new(cn: ControlNexus, provider: window::SharedProvider) -> Sources {
+    Sources {
+      cn,
+      provider,
+      generation: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  // replace the labels being updated with these.
+  pub fn start(&self, labels: Vec<BL::SourcedLabel>) {
+    let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    for l in labels {
+      let current = self.generation.clone();
+      let cn = self.cn.clone();
+      let provider = self.provider.clone();
+      thread::spawn(move || {
+        let interval = Duration::from_millis(l.interval.unwrap_or(DEFAULT_INTERVAL));
+        let mut shown = None;
+        loop {
+          let text = read(&l.source, &provider);
+          if current.load(Ordering::SeqCst) != generation {
+            return;
+          }
+          if shown.as_ref() != Some(&text) {
+            cn.update_label(l.name.as_str(), text.as_str());
+            shown = Some(text);
+          }
+          thread::sleep(interval);
+        }
+      });
+    }
+  }
+}
+
+// the text to show for a source.
+fn read(source: &BL::LabelSource, provider: &window::SharedProvider) -> String {
+  match source {
+    BL::LabelSource::Command {
+      program,
+      args,
+      timeout,
+    } => {
+      let mut cmd = Command::new(program);
+      cmd.args(args);
+      let timeout = Duration::from_millis(timeout.unwrap_or(DEFAULT_TIMEOUT));
+      let (status, out) = commands::output(cmd, timeout);
+      let out = out.trim().to_string();
+      match status.as_str() {
+        "timed out" => status,
+        "exit 0" => out,
+        _ if out.is_empty() => status,
+        _ => out,
+      }
+    }
+    BL::LabelSource::File(f) => match fs::read_to_string(f) {
+      Ok(s) => s.trim().to_string(),
+      Err(e) => format!("error: {}", e),
+    },
+    // a bad format is an error rather than a panic.
+    BL::LabelSource::Clock(format) => {
+      let mut s = String::new();
+      match write!(s, "{}", chrono::Local::now().format(format.as_str())) {
+        Ok(()) => s,
+        Err(_) => format!("bad clock format: {}", format),
+      }
+    }
+    BL::LabelSource::ActiveWindow => match window::lock(provider).active() {
+      Ok(Some(w)) => w.title,
+      Ok(None) => String::new(),
+      Err(e) => format!("error: {}", e),
+    },
+    BL::LabelSource::Battery => battery(),
+  }
+}
+
+// the first battery in /sys, like "87% Charging".
+#[cfg(target_os = "linux")]
+fn battery() -> String {
+  let dirs = match fs::read_dir("/sys/class/power_supply") {
+    Ok(d) => d,
+    Err(e) => return format!("error: {}", e),
+  };
+  let mut dirs: Vec<_> = dirs.filter_map(|d| d.ok()).map(|d| d.path()).collect();
+  dirs.sort();
+  for d in dirs {
+    let read = |f: &str| {
+      fs::read_to_string(d.join(f))
+        .map(|s| s.trim().to_string())
+        .unwrap_or(String::new())
+    };
+    if read("type") == "Battery" {
+      return format!("{}% {}", read("capacity"), read("status"))
+        .trim()
+        .to_string();
+    }
+  }
+  "no battery".to_string()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn battery() -> String {
+  "no battery".to_string()
+}
@@ -0,0 +1,193 @@
+// ---------------------------------------------------------------
+// the window with the focus on the host, from the window manager's
+// _NET_ACTIVE_WINDOW on X11, or from a file some script keeps up to
+// date.  the file is also handy as a fake window for trying out
+// rules.
+// ---------------------------------------------------------------
+use crate::buildlisp as BL;
+use failure::Error as FError;
+use std::fs;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[cfg(not(target_os = "linux"))]
+use failure::err_msg;
+
+#[cfg(target_os = "linux")]
+use x11rb::connection::Connection;
+#[cfg(target_os = "linux")]
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt, Window};
+#[cfg(target_os = "linux")]
+use x11rb::rust_connection::RustConnection;
+
+pub struct ActiveWindow {
+  pub title: String,
+  // the second part of WM_CLASS, like "vlc" or "Audacity".
+  pub class: String,
+}
+
+pub trait Provider: Send {
+  // None if nothing has the focus.
+  fn active(&mut self) -> Result<Option<ActiveWindow>, FError>;
+}
+
+pub type SharedProvider = Arc<Mutex<Box<dyn Provider>>>;
+
+pub fn lock(provider: &SharedProvider) -> MutexGuard<'_, Box<dyn Provider>> {
+  match provider.lock() {
+    Ok(guard) => guard,
+    Err(poisoned) => poisoned.into_inner(),
+  }
+}
+
+// X11 if the prefs don't say.
+pub fn new_shared(provider: &Option<BL::WindowProvider>) -> SharedProvider {
+  let p: Box<dyn Provider> = match provider {
+    Some(BL::WindowProvider::File(path)) => Box::new(WindowFile { path: path.clone() }),
+    Some(BL::WindowProvider::X11) | None => Box::new(X11::new()),
+  };
+  Arc::new(Mutex::new(p))
+}
+
+// the class on the first line and the title on the second.  an empty or
+// missing file is no window.
+pub struct WindowFile {
+  path: String,
+}
+
+impl Provider for WindowFile {
+  fn active(&mut self) -> Result<Option<ActiveWindow>, FError> {
+    let s = match fs::read_to_string(self.path.as_str()) {
+      Ok(s) => s,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+      Err(e) => return Err(e.into()),
+    };
+    let mut lines = s.lines();
+    let class = lines.next().unwrap_or("").trim().to_string();
+    let title = lines.next().unwrap_or("").trim().to_string();
+    if class.is_empty() && title.is_empty() {
+      Ok(None)
+    } else {
+      Ok(Some(ActiveWindow { title, class }))
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+struct Atoms {
+  net_active_window: Atom,
+  net_wm_name: Atom,
+  utf8_string: Atom,
+}
+
+// an X connection, made when first needed and again after errors.
+pub struct X11 {
+  #[cfg(target_os = "linux")]
+  conn: Option<(RustConnection, Window, Atoms)>,
+}
+
+impl X11 {
+  pub fn new() -> X11 {
+    X11 {
+      #[cfg(target_os = "linux")]
+      conn: None,
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+impl X11 {
+  fn connect() -> Result<(RustConnection, Window, Atoms), FError> {
+    let (conn, screen) = x11rb::connect(None)?;
+    let root = conn.setup().roots[screen].root;
+    let intern = |name: &str| -> Result<Atom, FError> {
+      Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+    };
+    let atoms = Atoms {
+      net_active_window: intern("_NET_ACTIVE_WINDOW")?,
+      net_wm_name: intern("_NET_WM_NAME")?,
+      utf8_string: intern("UTF8_STRING")?,
+    };
+    Ok((conn, root, atoms))
+  }
+
+  fn find(&mut self) -> Result<Option<ActiveWindow>, FError> {
+    if self.conn.is_none() {
+      self.conn = Some(X11::connect()?);
+    }
+    let result = match &self.conn {
+      Some((conn, root, atoms)) => active_window(conn, *root, atoms),
+      None => Ok(None),
+    };
+    // the X server may have gone away; connect again next time.
+    if result.is_err() {
+      self.conn = None;
+    }
+    result
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn property(
+  conn: &RustConnection,
+  window: Window,
+  property: Atom,
+  kind: Atom,
+) -> Result<Vec<u8>, FError> {
+  Ok(
+    conn
+      .get_property(false, window, property, kind, 0, 1024)?
+      .reply()?
+      .value,
+  )
+}
+
+#[cfg(target_os = "linux")]
+fn active_window(
+  conn: &RustConnection,
+  root: Window,
+  atoms: &Atoms,
+) -> Result<Option<ActiveWindow>, FError> {
+  let reply = conn
+    .get_property(false, root, atoms.net_active_window, AtomEnum::WINDOW, 0, 1)?
+    .reply()?;
+  let window = match reply.value32().and_then(|mut v| v.next()) {
+    Some(w) if w != 0 => w,
+    _ => return Ok(None),
+  };
+
+  // _NET_WM_NAME is utf8; older programs only set WM_NAME.
+  let mut title = property(conn, window, atoms.net_wm_name, atoms.utf8_string)?;
+  if title.is_empty() {
+    title = property(
+      conn,
+      window,
+      AtomEnum::WM_NAME.into(),
+      AtomEnum::STRING.into(),
+    )?;
+  }
+  // "instance\0class\0"
+  let class = property(
+    conn,
+    window,
+    AtomEnum::WM_CLASS.into(),
+    AtomEnum::STRING.into(),
+  )?;
+  let class = class.split(|b| *b == 0).nth(1).unwrap_or(&[]);
+  Ok(Some(ActiveWindow {
+    title: String::from_utf8_lossy(&title).to_string(),
+    class: String::from_utf8_lossy(class).to_string(),
+  }))
+}
+
+#[cfg(not(target_os = "linux"))]
+impl X11 {
+  fn find(&mut self) -> Result<Option<ActiveWindow>, FError> {
+    Err(err_msg("the active window is only known on linux with X11"))
+  }
+}
+
+impl Provider for X11 {
+  fn active(&mut self) -> Result<Option<ActiveWindow>, FError> {
+    self.find()
+  }
+}